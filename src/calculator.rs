@@ -0,0 +1,391 @@
+use std::{collections::HashMap, rc::Rc};
+
+use rug::Float;
+
+use crate::{
+    interpreter::{
+        ast::Stmt,
+        env::{Env, EnvMember, Func, UserFn},
+        error::Error,
+        native::{Arity, Ctx, NativeFn},
+        parser::Parser,
+        printer::InfixPrinter,
+        value::Value,
+        Interpreter,
+    },
+    scanner::{Scanner, Token, TokenKind},
+};
+
+/// A calculator session: everything defined so far, and the last result as `ans`.
+pub struct Calculator {
+    interpreter: Interpreter,
+}
+
+impl Default for Calculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calculator {
+    /// Creates a session with only the builtin constants and functions defined.
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Evaluates a line of `;`-separated statements and returns the value of the last one.
+    /// Assignments are kept for later evaluations. A comment after an assignment becomes the
+    /// docstring of what it defines, as in `hyp(a, b) = sqrt(a^2 + b^2)  # hypotenuse`.
+    pub fn eval(&mut self, input: &str) -> Result<Value, Error> {
+        self.run(input, true)
+    }
+
+    /// Like [`eval`](Self::eval), but leaves the session untouched. Useful to show results
+    /// while input is still being typed.
+    pub fn preview(&mut self, input: &str) -> Result<Value, Error> {
+        self.run(input, false)
+    }
+
+    fn run(&mut self, input: &str, save_assignments: bool) -> Result<Value, Error> {
+        let tokens = scan(input)?;
+        let program = Parser::new(&tokens, input).parse()?;
+        let defined = match program.stmts.last() {
+            Some(Stmt::VarAssign(a)) => Some(a.name.clone()),
+            Some(Stmt::FnAssign(a)) => Some(a.name.clone()),
+            _ => None,
+        };
+        self.interpreter.save_assignments = save_assignments;
+        let res = self.interpreter.run_program(program);
+        self.interpreter.save_assignments = true;
+        if let (Some(name), Some(doc), Ok(_), true) =
+            (defined, docstring(input, &tokens), &res, save_assignments)
+        {
+            self.interpreter.env.set_doc(&name, doc);
+        }
+        res
+    }
+
+    /// Defines `name(params...) = body`, replacing anything already called `name`. Error
+    /// offsets point into `body`.
+    pub fn define(&mut self, name: &str, params: &[&str], body: &str) -> Result<(), Error> {
+        let tokens = scan(body)?;
+        let expr = Parser::new(&tokens, body).parse_expr()?;
+        let f = Func::UserFn(UserFn {
+            expr: Rc::new(expr),
+            arguments: params.iter().map(|p| p.to_string()).collect(),
+            captures: HashMap::new(),
+        });
+        self.interpreter
+            .env
+            .set_func(name.to_string(), f)
+            .map_err(|message| Error { message, offset: 0 })
+    }
+
+    /// Makes a Rust closure callable as `name(...)`, replacing anything already called `name`.
+    /// `help` is a one-line description shown alongside the function.
+    ///
+    /// ```
+    /// use ferricalc::{disp_value, native::Arity, Calculator, Value, PREC_BITS};
+    /// use rug::{ops::Pow, Float};
+    ///
+    /// let mut calc = Calculator::new();
+    /// calc.register_fn("dbm_to_mw", Arity::Exact(1), "Converts dBm to mW", |_, args| {
+    ///     let dbm = args[0].clone().into_number()?;
+    ///     Ok(Value::Number(Float::with_val(PREC_BITS, 10).pow(dbm / 10)))
+    /// });
+    /// assert_eq!(disp_value(&calc.eval("dbm_to_mw(30)").unwrap(), 16), "1000");
+    /// ```
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        help: &str,
+        f: impl Fn(&mut Ctx, &[Value]) -> Result<Value, Error> + 'static,
+    ) {
+        self.register(NativeFn::new(name, arity, help, f));
+    }
+
+    /// Like [`register_fn`](Self::register_fn), for a function built with its parameter
+    /// names or docs.
+    pub fn register(&mut self, f: NativeFn) {
+        self.interpreter.env.register(f);
+    }
+
+    /// Calls a function value, e.g. one that was passed to a native function.
+    pub fn call(&mut self, f: &Func, args: Vec<Value>) -> Result<Value, Error> {
+        self.interpreter
+            .call("<lambda>", f, args)
+            .map_err(Error::from)
+    }
+
+    /// Sets the variable `name`, replacing a function already called `name`.
+    pub fn set_var(&mut self, name: &str, value: Float) -> Result<(), Error> {
+        self.interpreter
+            .env
+            .set_var(name.to_string(), value)
+            .map_err(|message| Error { message, offset: 0 })
+    }
+
+    /// Deletes the variable or function `name`, builtins included.
+    pub fn remove(&mut self, name: &str) -> Result<(), Error> {
+        self.interpreter
+            .env
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| Error {
+                message: format!("Nothing is called '{name}'"),
+                offset: 0,
+            })
+    }
+
+    /// All variables, builtin constants included, in alphabetical order.
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &Float)> {
+        self.interpreter.env.vars()
+    }
+
+    /// Statements that recreate every variable and function defined in the session, one per
+    /// line. Builtins are left out unless they have been redefined, and so are the functions
+    /// listed by [`unsaved`](Self::unsaved).
+    pub fn export(&self) -> String {
+        let builtins = Interpreter::new().env;
+        self.env()
+            .iter()
+            .filter(|(name, member)| match member {
+                EnvMember::Var(v) => builtins.get_var(name) != Some(v),
+                EnvMember::Fn(_) => true,
+            })
+            .filter_map(|(name, member)| {
+                let def = InfixPrinter::member(name, member)?;
+                Some(match self.env().doc(name) {
+                    Some(doc) => format!("{def}  # {doc}\n"),
+                    None => def + "\n",
+                })
+            })
+            .collect()
+    }
+
+    /// Functions [`export`](Self::export) leaves out because they capture something that
+    /// can't be written down, such as a list.
+    pub fn unsaved(&self) -> Vec<&str> {
+        self.env()
+            .iter()
+            .filter(|(_, member)| matches!(member, EnvMember::Fn(Func::UserFn(_))))
+            .filter(|(name, member)| InfixPrinter::member(name, member).is_none())
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// The result of the last evaluated statement.
+    pub fn ans(&self) -> &Value {
+        &self.interpreter.last_ans
+    }
+
+    /// Every result so far, `$1` being the first.
+    pub fn results(&self) -> &[Value] {
+        &self.interpreter.results
+    }
+
+    /// Forgets every result after the first `len`, so the next one is `$len + 1`.
+    pub fn truncate_results(&mut self, len: usize) {
+        self.interpreter.results.truncate(len);
+    }
+
+    /// Everything defined in the session, builtins included.
+    pub fn env(&self) -> &Env {
+        &self.interpreter.env
+    }
+}
+
+/// The text of a comment ending the input, if any. Line breaks after it don't count.
+fn docstring<'a>(input: &'a str, tokens: &[Token]) -> Option<&'a str> {
+    let last = tokens
+        .iter()
+        .rev()
+        .find(|t| !matches!(t.kind, TokenKind::Eof | TokenKind::Newline))?;
+    let comment = &input[last.span()];
+    let text = comment
+        .strip_prefix("//")
+        .unwrap_or(comment)
+        .trim_start_matches('#')
+        .trim();
+    (last.kind == TokenKind::Comment && !text.is_empty()).then_some(text)
+}
+
+fn scan(input: &str) -> Result<Vec<Token>, Error> {
+    Scanner::new(input)
+        .scan_tokens()
+        .map_err(|message| Error { message, offset: 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::disp_value, PREC_BITS};
+
+    fn eval(calc: &mut Calculator, input: &str) -> String {
+        disp_value(&calc.eval(input).unwrap(), 16)
+    }
+
+    #[test]
+    fn session() {
+        let mut calc = Calculator::new();
+        calc.set_var("k", Float::with_val(PREC_BITS, 3)).unwrap();
+        calc.define("f", &["x"], "k * x").unwrap();
+        assert_eq!(eval(&mut calc, "f(2)"), "6");
+        assert_eq!(disp_value(calc.ans(), 16), "6");
+        calc.define("k", &["x"], "x + 1").unwrap();
+        assert_eq!(eval(&mut calc, "k(1)"), "2");
+        calc.set_var("k", Float::with_val(PREC_BITS, 3)).unwrap();
+        assert_eq!(eval(&mut calc, "f(2)"), "6");
+        assert_eq!(calc.define("g", &["x"], "x +").unwrap_err().offset, 3);
+
+        assert!(calc.preview("tmp = 1").is_ok());
+        assert!(calc.eval("tmp").is_err());
+        let vars: Vec<_> = calc.vars().map(|(name, _)| name).collect();
+        assert_eq!(vars, ["e", "k", "pi", "π"]);
+    }
+
+    #[test]
+    fn preview_matches_eval() {
+        let mut calc = Calculator::new();
+        calc.eval("g(x) = x * k").unwrap();
+        for input in [
+            "k = 2; g(3)",
+            "c = 10; f(x) = x + c; f(1)",
+            "h = x -> x + 1; map(h, 1, 2)",
+            "let k = 5 in g(1)",
+            "g = 1",
+            "p = x -> x; p = 1",
+            "q = 1; q(x) = x",
+            "r(x) = x; r = 2; r(1)",
+        ] {
+            let preview = calc.preview(input).map(|v| disp_value(&v, 16));
+            let res = calc.eval(input).map(|v| disp_value(&v, 16));
+            assert_eq!(
+                preview.map_err(|e| e.message),
+                res.map_err(|e| e.message),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let mut calc = Calculator::new();
+        for line in [
+            "third = 1/3",
+            "neg = -2.5e-40",
+            "e = 3",
+            "k = 2",
+            "f(x, y) = (x + k) * -y^2 / 4 - 1",
+            "g(x) := k * x",
+            "adder(n) = x -> x + n",
+            "add2 = adder(2)",
+            "sq = map(x -> x^2, 1, 2)",
+            "h = compose(g, sqrt)",
+            "w(x) = let a = x, b = a + 1 in a * b",
+            "k = 5  # spring constant",
+        ] {
+            let _ = calc.eval(line);
+        }
+        let exported = calc.export();
+        assert!(!exported.contains("pi ="), "{exported}");
+        let mut restored = Calculator::new();
+        for line in exported.lines() {
+            restored.eval(line).unwrap();
+        }
+        assert_eq!(restored.export(), exported);
+        assert_eq!(restored.env().doc("k"), Some("spring constant"));
+        assert!(calc.unsaved().is_empty());
+        for input in [
+            "third * 3",
+            "neg",
+            "e",
+            "f(1, 2)",
+            "g(1)",
+            "add2(1)",
+            "h(16)",
+            "w(2)",
+        ] {
+            assert_eq!(
+                eval(&mut restored, input),
+                eval(&mut calc, input),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn export_skips_list_captures() {
+        let mut calc = Calculator::new();
+        for line in [
+            "mk(l) = y -> y + l",
+            "h = mk(map(x -> x, 1, 2))",
+            "s = mk(1)",
+        ] {
+            calc.eval(line).unwrap();
+        }
+        let exported = calc.export();
+        assert!(!exported.contains("h("), "{exported}");
+        assert!(exported.contains("s(y) = let l = 1 in y + l"), "{exported}");
+        assert_eq!(calc.unsaved(), ["h"]);
+    }
+
+    #[test]
+    fn native_fns() {
+        let mut calc = Calculator::new();
+        calc.register_fn("twice", Arity::Exact(2), "Calls f twice", |ctx, args| {
+            let f = args[0].clone().into_func()?;
+            let once = ctx.call(&f, vec![args[1].clone()])?;
+            ctx.call(&f, vec![once])
+        });
+        calc.register_fn("k", Arity::Exact(0), "", |ctx, _| {
+            ctx.get("k0").ok_or("k0 is not set".into())
+        });
+        assert_eq!(eval(&mut calc, "twice(x -> x * 3, 2)"), "18");
+        assert_eq!(
+            calc.eval("twice(sqrt)").unwrap_err().message,
+            "Function 'twice' takes 2 args"
+        );
+        calc.eval("k0 = 7").unwrap();
+        assert_eq!(eval(&mut calc, "k() + 1"), "8");
+    }
+
+    #[test]
+    fn docs() {
+        let mut calc = Calculator::new();
+        calc.eval("hyp(a, b) = sqrt(a^2 + b^2)  # hypotenuse")
+            .unwrap();
+        assert_eq!(calc.env().help("hyp"), Some("hypotenuse"));
+        calc.preview("hyp(a, b) = a  # not saved").unwrap();
+        assert_eq!(calc.env().help("hyp"), Some("hypotenuse"));
+        calc.eval("hyp(a, b) = a + b").unwrap();
+        assert_eq!(calc.env().help("hyp"), None);
+        calc.eval("hyp(a, b) = a + b  # sum").unwrap();
+        calc.remove("hyp").unwrap();
+        assert!(calc.remove("hyp").is_err());
+        calc.eval("hyp = 1").unwrap();
+        assert_eq!(calc.env().help("hyp"), None);
+        calc.eval("sq(x) = x^2  # square\n").unwrap();
+        assert_eq!(calc.env().help("sq"), Some("square"));
+        calc.eval("half(x) = x / 2 // half").unwrap();
+        assert_eq!(calc.env().help("half"), Some("half"));
+        assert!(calc.export().contains("half(x) = x / 2  # half\n"));
+
+        let funcs: Vec<_> = calc
+            .env()
+            .iter()
+            .filter_map(|(name, m)| match m {
+                EnvMember::Fn(f) => Some((name.to_string(), f.doc())),
+                EnvMember::Var(_) => None,
+            })
+            .collect();
+        for (name, doc) in funcs {
+            for example in doc.examples {
+                assert!(calc.eval(example).is_ok(), "{name}: {example}");
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::{fs, path::PathBuf};
+
+use ferricalc::{format_num, Calculator, EnvMember};
+
+use crate::{config::set_format, help::help, plot, session, table, vi::Vi, App};
+
+/// Every command as its usage and what it does, for `:help` and the hint shown while typing.
+pub const COMMANDS: &[(&str, &str)] = &[
+    (
+        "help [name]",
+        "this overview, or help on a function or variable",
+    ),
+    ("vars", "every variable and its value"),
+    ("funcs", "every function and its definition or description"),
+    ("del name", "delete a variable or function"),
+    ("clear", "clear the screen"),
+    ("reset", "forget everything defined so far"),
+    (
+        "prec [digits]",
+        "show or set how many significant digits results get",
+    ),
+    (
+        "format [style]",
+        "show or set how results are written: auto, fixed n, sci, eng or si",
+    ),
+    (
+        "group [sep]",
+        "show or set what separates thousands: off, _, ',', ' or space",
+    ),
+    ("decimal [.|,]", "show or set the decimal separator"),
+    ("mode [emacs|vi]", "show or set the editing keys"),
+    (
+        "inspect",
+        "show or hide the sidebar of everything defined, also F2",
+    ),
+    ("history [n]", "the last n inputs"),
+    ("save [file]", "save the session's definitions"),
+    ("load [file]", "run a saved session"),
+    (
+        "autosave on|off",
+        "save the session on exit and restore it on start",
+    ),
+    (
+        "csv [file]",
+        "write the last table to a CSV file, table.csv by default",
+    ),
+    ("quit", "leave, like Esc"),
+];
+
+/// What to do once a command's message is shown.
+#[derive(Debug, PartialEq)]
+pub enum After {
+    Continue,
+    Clear,
+    Quit,
+}
+
+/// Runs a `:`-prefixed command, `cmd` being the text after the colon. The message is shown
+/// either way.
+pub fn run(cmd: &str, app: &mut App, calc: &mut Calculator) -> Result<(String, After), String> {
+    let mut words = cmd.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let arg = words.next();
+    let rest = cmd.trim_start()[name.len()..].trim();
+    let file = || match arg {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => session::default_session_file(),
+    };
+    let msg = match name {
+        "help" => help(arg, calc)?,
+        "vars" => calc
+            .vars()
+            .map(|(name, v)| {
+                let v = format_num(v, &app.format);
+                match calc.env().doc(name) {
+                    Some(doc) => format!("{name} = {v}  # {doc}"),
+                    None => format!("{name} = {v}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "funcs" => calc
+            .env()
+            .iter()
+            .filter_map(|(name, m)| {
+                let EnvMember::Fn(f) = m else { return None };
+                let def = m
+                    .definition(name)
+                    .unwrap_or_else(|| format!("{name}({})", f.params()));
+                Some(match calc.env().help(name) {
+                    Some(help) => format!("{def}  # {help}"),
+                    None => def,
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "del" => {
+            let name = arg.ok_or("Usage: :del name")?;
+            calc.remove(name).map_err(|e| e.message)?;
+            format!("Deleted '{name}'")
+        }
+        "clear" => return Ok((String::new(), After::Clear)),
+        "reset" => {
+            *calc = Calculator::new();
+            plot::register(calc, &app.plot_requested);
+            table::register(calc, &app.table_requested);
+            "Everything defined so far is forgotten".to_string()
+        }
+        "prec" => {
+            if let Some(arg) = arg {
+                set_format(&mut app.format, "digits", arg)?;
+            }
+            format!("Showing {} significant digits", app.format.digits)
+        }
+        "format" => {
+            if !rest.is_empty() {
+                set_format(&mut app.format, "format", rest)?;
+            }
+            format!("Writing results in {} format", app.format.notation)
+        }
+        "group" => {
+            if let Some(arg) = arg {
+                set_format(&mut app.format, "grouping", arg)?;
+            }
+            match app.format.grouping {
+                Some(' ') => "Separating thousands with spaces".to_string(),
+                Some(sep) => format!("Separating thousands with '{sep}'"),
+                None => "Not separating thousands".to_string(),
+            }
+        }
+        "decimal" => {
+            if let Some(arg) = arg {
+                set_format(&mut app.format, "decimal", arg)?;
+            }
+            format!("Writing decimals with '{}'", app.format.decimal)
+        }
+        "mode" => {
+            match arg {
+                Some("emacs") => app.vi = None,
+                Some("vi") => app.vi = app.vi.take().or_else(|| Some(Vi::default())),
+                Some(mode) => return Err(format!("Modes are 'emacs' and 'vi', not '{mode}'")),
+                None => {}
+            }
+            let mode = if app.vi.is_some() { "vi" } else { "emacs" };
+            format!("Editing with {mode} keys")
+        }
+        "inspect" => {
+            app.toggle_inspector();
+            String::new()
+        }
+        "history" => {
+            let n = arg.map_or(Ok(20), str::parse).map_err(|e| format!("{e}"))?;
+            let skip = app.history.len().saturating_sub(n);
+            let width = app.history.len().to_string().len();
+            app.history
+                .iter()
+                .enumerate()
+                .skip(skip)
+                .map(|(i, e)| match &e.result {
+                    Some(r) => format!("{:>width$}  {}  = {r}", i + 1, e.input),
+                    None => format!("{:>width$}  {}", i + 1, e.input),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        "save" => {
+            let path = file()?;
+            let n = session::save(calc, &path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let saved = format!("Saved {n} definitions to {}", path.display());
+            match calc.unsaved() {
+                unsaved if unsaved.is_empty() => saved,
+                unsaved => format!("{saved}, leaving out {}", unsaved.join(", ")),
+            }
+        }
+        "load" => {
+            let path = file()?;
+            let n = session::load(calc, &path)?;
+            format!("Loaded {n} definitions from {}", path.display())
+        }
+        "autosave" => match arg {
+            Some("on") => app.autosave.set_enabled(calc, true)?,
+            Some("off") => app.autosave.set_enabled(calc, false)?,
+            _ => format!(
+                "Auto-save is {}, use :autosave on|off",
+                if app.autosave.enabled { "on" } else { "off" }
+            ),
+        },
+        "csv" => {
+            let table = app
+                .table
+                .as_ref()
+                .ok_or("No table yet, make one with table(...)")?;
+            let path = arg.unwrap_or("table.csv");
+            fs::write(path, table.csv(app.format.digits)).map_err(|e| format!("{path}: {e}"))?;
+            format!("Wrote the table to {path}")
+        }
+        "quit" | "q" => return Ok((String::new(), After::Quit)),
+        _ => return Err(format!("Unknown command ':{name}', see :help")),
+    };
+    Ok((msg, After::Continue))
+}
+
+/// The commands starting with the word typed so far, shown while a command is typed.
+pub fn hint(cmd: &str) -> String {
+    let typed = cmd.split_whitespace().next().unwrap_or_default();
+    let found: Vec<_> = COMMANDS
+        .iter()
+        .filter(|(usage, _)| usage.starts_with(typed))
+        .collect();
+    match found[..] {
+        [] => format!("Unknown command ':{typed}'"),
+        [(usage, what)] => format!(":{usage}  {what}"),
+        _ => found
+            .iter()
+            .map(|(usage, _)| usage.split(' ').next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ferricalc::Notation;
+
+    use super::*;
+
+    fn run_ok(cmd: &str, app: &mut App, calc: &mut Calculator) -> String {
+        let (msg, after) = run(cmd, app, calc).unwrap();
+        assert_eq!(after, After::Continue);
+        msg
+    }
+
+    #[test]
+    fn commands() {
+        let mut app = App::default();
+        let mut calc = Calculator::new();
+        calc.eval("x = 1.5  # rate").unwrap();
+        calc.eval("f(y) = x * y").unwrap();
+        assert!(run_ok("vars", &mut app, &mut calc).contains("x = 1.5  # rate"));
+        let funcs = run_ok("funcs", &mut app, &mut calc);
+        assert!(funcs.contains("f(y) = x * y\n"), "{funcs}");
+        assert!(funcs.contains("sqrt(x)  # Square root"), "{funcs}");
+
+        run_ok("del x", &mut app, &mut calc);
+        assert!(calc.eval("f(2)").is_err());
+        assert!(run("del x", &mut app, &mut calc).is_err());
+        run_ok("reset", &mut app, &mut calc);
+        assert!(calc.env().get_func("f").is_none());
+
+        assert_eq!(
+            run_ok("prec 5", &mut app, &mut calc),
+            "Showing 5 significant digits"
+        );
+        assert!(run("prec 0", &mut app, &mut calc).is_err());
+        run_ok("format fixed 3", &mut app, &mut calc);
+        assert_eq!(app.format.notation, Notation::Fixed(3));
+        run_ok("group _", &mut app, &mut calc);
+        run_ok("decimal ,", &mut app, &mut calc);
+        calc.eval("y = 1234.5").unwrap();
+        assert!(run_ok("vars", &mut app, &mut calc).contains("y = 1_234,500"));
+        assert!(run("format hex", &mut app, &mut calc).is_err());
+        run_ok("mode vi", &mut app, &mut calc);
+        assert!(app.vi.is_some());
+        assert_eq!(run("quit", &mut app, &mut calc).unwrap().1, After::Quit);
+        assert!(run("frobnicate", &mut app, &mut calc).is_err());
+    }
+
+    #[test]
+    fn hints() {
+        assert_eq!(hint("del"), ":del name  delete a variable or function");
+        assert_eq!(hint("de"), "del decimal");
+        assert_eq!(hint("h"), "help history");
+        assert_eq!(hint("zz"), "Unknown command ':zz'");
+    }
+}
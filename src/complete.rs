@@ -0,0 +1,157 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use ferricalc::{disp_num, Env, EnvMember, Scanner, TokenKind};
+
+use crate::config::Matching;
+
+/// Digits shown for a variable's value in the completion popup.
+const COMPLETION_DIGITS: usize = 12;
+
+pub struct Candidate {
+    pub name: String,
+    pub is_fn: bool,
+    /// The current value of a variable, or the signature of a function.
+    pub detail: String,
+    pub help: Option<String>,
+}
+
+impl Candidate {
+    fn new(name: &str, member: &EnvMember, env: &Env) -> Self {
+        let (is_fn, detail) = match member {
+            EnvMember::Var(v) => (false, disp_num(v, COMPLETION_DIGITS).unwrap_or_default()),
+            EnvMember::Fn(f) => (true, format!("{name}({})", f.params())),
+        };
+        Self {
+            name: name.to_string(),
+            is_fn,
+            detail,
+            help: env.help(name).map(str::to_string),
+        }
+    }
+}
+
+pub struct Completion {
+    pub index: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+impl Completion {
+    /// Members of `env` matching `query`, best first, or `None` if there are none.
+    pub fn new(query: &str, env: &Env, matching: Matching, recency: &Recency) -> Option<Self> {
+        let mut found: Vec<_> = match matching {
+            Matching::Prefix => env.search(query).map(|(name, m)| (0, name, m)).collect(),
+            Matching::Fuzzy => env
+                .iter()
+                .filter_map(|(name, m)| fuzzy_score(query, name).map(|s| (s, name, m)))
+                .collect(),
+        };
+        found.sort_by_key(|&(score, name, _)| {
+            (
+                !name.eq_ignore_ascii_case(query),
+                Reverse(score),
+                Reverse(recency.last_used(name)),
+                name.len(),
+            )
+        });
+        let candidates: Vec<_> = found
+            .into_iter()
+            .map(|(_, name, m)| Candidate::new(name, m, env))
+            .collect();
+        (!candidates.is_empty()).then_some(Self {
+            index: 0,
+            candidates,
+        })
+    }
+}
+
+/// Scores `name` if the characters of `query` appear in it in order, ignoring case. Matches at
+/// the start score highest, then ones right after a `_` or next to the previous match.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<u32> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut prev_matched = false;
+    let mut prev = None;
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let Some(&q) = query.peek() else { break };
+        if c == q {
+            query.next();
+            score += match prev {
+                None => 17,
+                Some('_') => 9,
+                _ => 1,
+            };
+            if prev_matched {
+                score += 4;
+            }
+        }
+        prev_matched = c == q;
+        prev = Some(c);
+    }
+    query.peek().is_none().then_some(score)
+}
+
+/// When each name was last used, so recently used names are offered first.
+#[derive(Default)]
+pub struct Recency {
+    clock: u64,
+    used: HashMap<String, u64>,
+}
+
+impl Recency {
+    pub fn last_used(&self, name: &str) -> u64 {
+        self.used.get(name).copied().unwrap_or(0)
+    }
+
+    /// Marks the identifiers in `source` as just used.
+    pub fn use_source(&mut self, source: &str) {
+        let Ok(tokens) = Scanner::new(source).scan_tokens() else {
+            return;
+        };
+        self.clock += 1;
+        for t in tokens.iter().filter(|t| t.kind == TokenKind::Indentifier) {
+            self.used.insert(source[t.span()].to_string(), self.clock);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rug::Float;
+
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert!(fuzzy_score("hyp", "hypot").is_some());
+        assert!(fuzzy_score("sqt", "sqrt").is_some());
+        assert!(fuzzy_score("SQ", "sqrt").is_some());
+        assert_eq!(fuzzy_score("tq", "sqrt"), None);
+        assert_eq!(fuzzy_score("sqrtx", "sqrt"), None);
+        assert!(fuzzy_score("su", "sum").unwrap() > fuzzy_score("su", "sinus").unwrap());
+        assert!(fuzzy_score("mr", "max_rate").unwrap() > fuzzy_score("mr", "mirror").unwrap());
+        assert!(fuzzy_score("rate", "max_rate").is_some());
+        assert!(fuzzy_score("ra", "rate").unwrap() > fuzzy_score("ra", "max_rate").unwrap());
+    }
+
+    #[test]
+    fn ranks() {
+        let mut env = Env::new();
+        for name in ["sin", "sum", "is_set", "pi", "pi_half"] {
+            env.set_var(name, Float::with_val(53, 1)).unwrap();
+        }
+        let names =
+            |c: Completion| -> Vec<_> { c.candidates.into_iter().map(|c| c.name).collect() };
+        let mut recency = Recency::default();
+        let c = Completion::new("s", &env, Matching::Prefix, &recency).unwrap();
+        assert_eq!(names(c), ["sin", "sum"]);
+        recency.use_source("sum(2, 3)");
+        let c = Completion::new("s", &env, Matching::Fuzzy, &recency).unwrap();
+        assert_eq!(names(c), ["sum", "sin", "is_set"]);
+        assert!(Completion::new("x", &env, Matching::Fuzzy, &recency).is_none());
+        recency.use_source("pi_half * 2");
+        for matching in [Matching::Prefix, Matching::Fuzzy] {
+            let c = Completion::new("pi", &env, matching, &recency).unwrap();
+            assert_eq!(names(c), ["pi", "pi_half"]);
+        }
+    }
+}
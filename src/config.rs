@@ -0,0 +1,183 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use ferricalc::{Format, MAX_DIGITS};
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum EditMode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+/// How typed names are matched against completion candidates.
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum Matching {
+    Prefix,
+    /// The typed characters appear in order, e.g. `sqt` for `sqrt`.
+    #[default]
+    Fuzzy,
+}
+
+/// Settings from `$XDG_CONFIG_HOME/ferricalc/config`, written as `key = value` lines.
+#[derive(Default, Debug)]
+pub struct Config {
+    pub edit_mode: EditMode,
+    /// Type `)` along with `(`.
+    pub auto_pair: bool,
+    pub completion: Matching,
+    /// How results are written out, set with `format`, `digits`, `grouping` and `decimal`.
+    pub format: Format,
+}
+
+/// `$XDG_CONFIG_HOME/ferricalc`, falling back to `~/.config/ferricalc`.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+        .map(|d| d.join("ferricalc"))
+}
+
+impl Config {
+    /// Reads the config file, which doesn't have to exist.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = config_dir().map(|d| d.join("config")) else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(source) => Self::parse(&source).map_err(|e| format!("{}:{e}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or(format!("{}: expected 'key = value'", i + 1))?;
+            config
+                .set(key, value)
+                .map_err(|e| format!("{}: {e}", i + 1))?;
+        }
+        Ok(config)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "edit_mode" => {
+                self.edit_mode = match value {
+                    "emacs" => EditMode::Emacs,
+                    "vi" => EditMode::Vi,
+                    _ => return Err(format!("edit_mode is 'emacs' or 'vi', not '{value}'")),
+                }
+            }
+            "auto_pair" => {
+                self.auto_pair = value
+                    .parse()
+                    .map_err(|_| format!("auto_pair is 'true' or 'false', not '{value}'"))?
+            }
+            "completion" => {
+                self.completion = match value {
+                    "prefix" => Matching::Prefix,
+                    "fuzzy" => Matching::Fuzzy,
+                    _ => return Err(format!("completion is 'prefix' or 'fuzzy', not '{value}'")),
+                }
+            }
+            "format" | "digits" | "grouping" | "decimal" => {
+                set_format(&mut self.format, key, value)?
+            }
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+        Ok(())
+    }
+}
+
+/// Sets one of the settings in `format`, which can also be changed with commands while running.
+pub fn set_format(format: &mut Format, key: &str, value: &str) -> Result<(), String> {
+    let mut new = *format;
+    match key {
+        "format" => new.notation = value.parse()?,
+        "digits" => {
+            new.digits = match value.parse() {
+                Ok(n) if (1..=MAX_DIGITS).contains(&n) => n,
+                _ => return Err(format!("digits go from 1 to {MAX_DIGITS}, not '{value}'")),
+            }
+        }
+        "grouping" => {
+            new.grouping = match value {
+                "off" => None,
+                "space" => Some(' '),
+                "_" | "," | "'" => value.chars().next(),
+                _ => {
+                    return Err(format!(
+                        "grouping is off, _, ',', ' or space, not '{value}'"
+                    ))
+                }
+            }
+        }
+        "decimal" => {
+            new.decimal = match value {
+                "." | "," => value.chars().next().unwrap(),
+                _ => return Err(format!("decimal is '.' or ',', not '{value}'")),
+            }
+        }
+        _ => return Err(format!("unknown setting '{key}'")),
+    }
+    if new.grouping == Some(new.decimal) {
+        return Err(format!(
+            "grouping and decimal can't both be '{}'",
+            new.decimal
+        ));
+    }
+    *format = new;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ferricalc::Notation;
+
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let config =
+            Config::parse("# settings\n\nedit_mode = vi # modal\nauto_pair = true").unwrap();
+        assert_eq!(config.edit_mode, EditMode::Vi);
+        assert!(config.auto_pair);
+        assert_eq!(config.completion, Matching::Fuzzy);
+        let config = Config::parse("completion = prefix").unwrap();
+        assert_eq!(config.completion, Matching::Prefix);
+        assert_eq!(
+            Config::parse("\nedit_mode = ed").unwrap_err(),
+            "2: edit_mode is 'emacs' or 'vi', not 'ed'"
+        );
+        assert!(Config::parse("colour").is_err());
+
+        let config = Config::parse("format = fixed 3\ngrouping = space\ndecimal = ,").unwrap();
+        assert_eq!(config.format.notation, Notation::Fixed(3));
+        assert_eq!(config.format.grouping, Some(' '));
+        assert_eq!(config.format.decimal, ',');
+        assert!(Config::parse("digits = 100").is_err());
+        assert!(Config::parse("grouping = ,").is_ok());
+        assert!(Config::parse("format = fixed 100").is_err());
+        assert!(Config::parse("decimal = ,\ngrouping = ,").is_err());
+        assert!(Config::parse("grouping = ,\ndecimal = ,").is_err());
+        let mut format = Format {
+            grouping: Some(','),
+            ..Format::default()
+        };
+        assert!(set_format(&mut format, "decimal", ",").is_err());
+        assert_eq!(format.decimal, '.');
+    }
+}
@@ -0,0 +1,309 @@
+use std::{collections::VecDeque, ops::Range};
+
+use ferricalc::{is_ident_char, is_ident_start, Token, TokenKind};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What the previous key did, for the edits that behave differently when repeated.
+#[derive(Default, Clone, PartialEq)]
+pub enum LastAction {
+    #[default]
+    Other,
+    Insert,
+    Kill,
+    /// The range of the text that was yanked.
+    Yank(Range<usize>),
+}
+
+const KILL_RING_CAP: usize = 32;
+
+/// Killed text, newest first. Consecutive kills are joined into one entry like in readline.
+#[derive(Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+    index: usize,
+}
+
+impl KillRing {
+    /// Adds `text`, joining it to the newest entry when `extend` is set. `before` says the text
+    /// was in front of the cursor, so it goes in front of that entry.
+    pub fn kill(&mut self, text: String, extend: bool, before: bool) {
+        match self.entries.front_mut() {
+            Some(top) if extend && before => *top = text + top,
+            Some(top) if extend => *top += &text,
+            _ if text.is_empty() => {}
+            _ => {
+                self.entries.push_front(text);
+                self.entries.truncate(KILL_RING_CAP);
+            }
+        }
+        self.index = 0;
+    }
+
+    pub fn yank(&mut self) -> Option<&str> {
+        self.index = 0;
+        self.entries.front().map(String::as_str)
+    }
+
+    /// The entry before the one last yanked, wrapping around.
+    pub fn rotate(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.index = (self.index + 1) % self.entries.len();
+        self.entries.get(self.index).map(String::as_str)
+    }
+}
+
+/// The input and cursor position.
+pub type State = (String, usize);
+
+const UNDO_CAP: usize = 200;
+
+#[derive(Default)]
+pub struct Undo {
+    undo: Vec<State>,
+    redo: Vec<State>,
+}
+
+impl Undo {
+    /// Remembers the state before an edit.
+    pub fn save(&mut self, state: State) {
+        if self.undo.last() != Some(&state) {
+            self.undo.push(state);
+            if self.undo.len() > UNDO_CAP {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, current: State) -> Option<State> {
+        let state = self.undo.pop()?;
+        self.redo.push(current);
+        Some(state)
+    }
+
+    pub fn redo(&mut self, current: State) -> Option<State> {
+        let state = self.redo.pop()?;
+        self.undo.push(current);
+        Some(state)
+    }
+}
+
+/// Start of the grapheme before `pos`, so that the cursor never ends up inside a character.
+pub fn prev_grapheme(s: &str, pos: usize) -> usize {
+    s[..pos]
+        .graphemes(true)
+        .next_back()
+        .map_or(pos, |g| pos - g.len())
+}
+
+pub fn next_grapheme(s: &str, pos: usize) -> usize {
+    s[pos..]
+        .graphemes(true)
+        .next()
+        .map_or(pos, |g| pos + g.len())
+}
+
+pub fn line_start(s: &str, pos: usize) -> usize {
+    s[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+pub fn line_end(s: &str, pos: usize) -> usize {
+    s[pos..].find('\n').map_or(s.len(), |i| pos + i)
+}
+
+/// The position `n` characters after `from`, but not past `limit`.
+fn nth_char(s: &str, from: usize, n: usize, limit: usize) -> usize {
+    s[from..limit]
+        .char_indices()
+        .nth(n)
+        .map_or(limit, |(i, _)| from + i)
+}
+
+/// The same column on the line above, or `None` on the first line.
+pub fn line_up(s: &str, pos: usize) -> Option<usize> {
+    let start = line_start(s, pos);
+    let prev = line_start(s, start.checked_sub(1)?);
+    Some(nth_char(s, prev, s[start..pos].chars().count(), start - 1))
+}
+
+/// The same column on the line below, or `None` on the last line.
+pub fn line_down(s: &str, pos: usize) -> Option<usize> {
+    let end = line_end(s, pos);
+    if end == s.len() {
+        return None;
+    }
+    let column = s[line_start(s, pos)..pos].chars().count();
+    Some(nth_char(s, end + 1, column, line_end(s, end + 1)))
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Start of the word before `pos`, skipping anything in between.
+pub fn prev_word(s: &str, pos: usize) -> usize {
+    s[..pos]
+        .trim_end_matches(|c| !is_word(c))
+        .trim_end_matches(is_word)
+        .len()
+}
+
+/// End of the word after `pos`, skipping anything in between.
+pub fn next_word(s: &str, pos: usize) -> usize {
+    let rest = s[pos..]
+        .trim_start_matches(|c| !is_word(c))
+        .trim_start_matches(is_word);
+    s.len() - rest.len()
+}
+
+/// How many parentheses are left open at the end of `tokens`.
+pub fn unclosed_parens(tokens: &[Token]) -> usize {
+    tokens.iter().fold(0, |depth: usize, t| match t.kind {
+        TokenKind::LParen => depth + 1,
+        TokenKind::RParen => depth.saturating_sub(1),
+        _ => depth,
+    })
+}
+
+/// Pairs up parentheses, returning the indices of each `(` and its `)` in `tokens`, and of
+/// those without a partner.
+pub fn match_parens(tokens: &[Token]) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut open = Vec::new();
+    for (i, t) in tokens.iter().enumerate() {
+        match t.kind {
+            TokenKind::LParen => open.push(i),
+            TokenKind::RParen => match open.pop() {
+                Some(start) => pairs.push((start, i)),
+                None => unmatched.push(i),
+            },
+            _ => {}
+        }
+    }
+    unmatched.extend(open);
+    (pairs, unmatched)
+}
+
+pub fn get_ident_range(input: &str, ident_end: usize) -> Option<Range<usize>> {
+    let mut last_char_index = None;
+    for (i, c) in input[..ident_end].char_indices().rev() {
+        if is_ident_start(c) {
+            last_char_index = Some(i);
+        }
+        if !is_ident_char(c) {
+            break;
+        }
+    }
+    last_char_index.map(|i| i..ident_end)
+}
+
+/// The whole identifier that `pos` is inside of or at the end of.
+pub fn get_ident_around(input: &str, pos: usize) -> Option<Range<usize>> {
+    let start = get_ident_range(input, pos)?.start;
+    let end = input[pos..]
+        .char_indices()
+        .find(|&(_, c)| !is_ident_char(c))
+        .map_or(input.len(), |(i, _)| pos + i);
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use ferricalc::Scanner;
+
+    use super::*;
+
+    #[test]
+    fn words() {
+        let s = "sqrt(foo_1 + 2)  ";
+        assert_eq!(prev_word(s, s.len()), 13);
+        assert_eq!(prev_word(s, 13), 5);
+        assert_eq!(prev_word(s, 7), 5);
+        assert_eq!(prev_word(s, 4), 0);
+        assert_eq!(prev_word(s, 0), 0);
+        assert_eq!(prev_word("  ", 2), 0);
+        assert_eq!(next_word(s, 0), 4);
+        assert_eq!(next_word(s, 4), 10);
+        assert_eq!(next_word(s, 14), s.len());
+    }
+
+    #[test]
+    fn graphemes() {
+        let s = "π×e\u{301}2";
+        assert_eq!(next_grapheme(s, 0), 2);
+        assert_eq!(next_grapheme(s, 2), 4);
+        assert_eq!(next_grapheme(s, 4), 7);
+        assert_eq!(prev_grapheme(s, 7), 4);
+        assert_eq!(prev_grapheme(s, s.len()), 7);
+        assert_eq!(next_grapheme(s, s.len()), s.len());
+        assert_eq!(prev_grapheme(s, 0), 0);
+        assert_eq!(prev_word("2×πr", "2×πr".len()), 3);
+    }
+
+    #[test]
+    fn lines() {
+        let s = "f(x) = (\n  x\n) + 1";
+        assert_eq!(line_start(s, 12), 9);
+        assert_eq!(line_end(s, 12), 12);
+        assert_eq!(line_up(s, 11), Some(2));
+        assert_eq!(line_up(s, 3), None);
+        assert_eq!(line_down(s, 3), Some(12));
+        assert_eq!(line_down(s, 11), Some(15));
+        assert_eq!(line_down(s, 15), None);
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut ring = KillRing::default();
+        ring.kill("a".into(), false, false);
+        ring.kill("b".into(), false, false);
+        ring.kill("c".into(), true, false);
+        ring.kill("0".into(), true, true);
+        assert_eq!(ring.yank(), Some("0bc"));
+        assert_eq!(ring.rotate(), Some("a"));
+        assert_eq!(ring.rotate(), Some("0bc"));
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut undo = Undo::default();
+        undo.save(("".into(), 0));
+        undo.save(("a".into(), 1));
+        assert_eq!(undo.undo(("ab".into(), 2)), Some(("a".into(), 1)));
+        assert_eq!(undo.undo(("a".into(), 1)), Some(("".into(), 0)));
+        assert_eq!(undo.undo(("".into(), 0)), None);
+        assert_eq!(undo.redo(("".into(), 0)), Some(("a".into(), 1)));
+        undo.save(("a".into(), 1));
+        assert_eq!(undo.redo(("ax".into(), 2)), None);
+    }
+
+    #[test]
+    fn matches_parens() {
+        let tokens = Scanner::new(") f((1), (2").scan_tokens().unwrap();
+        let (pairs, unmatched) = match_parens(&tokens);
+        assert_eq!(pairs, [(3, 5)]);
+        assert_eq!(unmatched, [0, 2, 7]);
+        assert_eq!(unclosed_parens(&tokens), 2);
+    }
+
+    #[test]
+    fn ident_around() {
+        assert_eq!(get_ident_around("2*sqrt(x)", 4), Some(2..6));
+        assert_eq!(get_ident_around("2*sqrt(x)", 6), Some(2..6));
+        assert_eq!(get_ident_around("2*sqrt(x)", 2), None);
+    }
+
+    #[test]
+    fn ident_at_end() {
+        fn at_end(s: &str) -> Option<&str> {
+            get_ident_range(s, s.len()).map(|r| &s[r])
+        }
+        assert_eq!(at_end("1abc"), Some("abc"));
+        assert_eq!(at_end("abc+bob1bob1"), Some("bob1bob1"));
+        assert_eq!(at_end("abc "), None);
+        assert_eq!(at_end("2*dbm_to"), Some("dbm_to"))
+    }
+}
@@ -0,0 +1,101 @@
+use ferricalc::{disp_num, disp_value, Calculator, EnvMember};
+
+use crate::commands::COMMANDS;
+
+/// Digits shown for values and example results in help text.
+const HELP_DIGITS: usize = 12;
+
+const OVERVIEW: &str = "\
+Type an expression and press Enter to evaluate it, e.g. 2 * sqrt(2).
+Assign with x = 1 or f(x) = x^2, and document it with a trailing # comment.
+$1, $2, … are earlier results. ?name is the same as :help name, and
+:help operators explains the syntax.";
+
+const OPERATORS: &str = "\
+From loosest to tightest binding:
+  a; b          runs both, giving b; a line break does the same
+  x = e         assigns; f(x) = e defines a function
+  x := e        like =, but functions keep the values of the globals they use
+  let x = e in  binds x in the expression after 'in'
+  x -> e        a function, also (x, y) -> e
+  a + b  a - b
+  a * b  a / b  also a × b, a · b and a ÷ b
+  -a  +a  √a
+  a ^ b         right associative, so 2^3^2 = 2^9 and -2^2 = -4; also a², a⁻¹
+  f(a, b)  (a)  calls and grouping
+Lists passed to functions are flattened into their arguments.
+# starts a comment that runs to the end of the line.";
+
+/// Help on `topic`, or an overview of the calculator without one.
+pub fn help(topic: Option<&str>, calc: &Calculator) -> Result<String, String> {
+    let Some(name) = topic else {
+        let funcs: Vec<_> = calc
+            .env()
+            .iter()
+            .filter(|(_, m)| matches!(m, EnvMember::Fn(_)))
+            .map(|(name, _)| name)
+            .collect();
+        let commands: Vec<_> = COMMANDS
+            .iter()
+            .map(|(usage, what)| format!("  :{usage:<18}{what}"))
+            .collect();
+        return Ok(format!(
+            "{OVERVIEW}\n\nCommands:\n{}\n\nFunctions:\n  {}",
+            commands.join("\n"),
+            funcs.join(", ")
+        ));
+    };
+    if name == "operators" {
+        return Ok(OPERATORS.to_string());
+    }
+    let member = calc
+        .env()
+        .iter()
+        .find(|&(n, _)| n == name)
+        .map(|(_, m)| m)
+        .ok_or(format!("Nothing is called '{name}'"))?;
+    let mut lines = match member {
+        EnvMember::Var(v) => vec![format!(
+            "{name} = {}",
+            disp_num(v, HELP_DIGITS).unwrap_or_default()
+        )],
+        EnvMember::Fn(f) => vec![format!("{name}({})", f.params())],
+    };
+    lines.extend(calc.env().help(name).map(|h| format!("  {h}")));
+    if let EnvMember::Fn(f) = member {
+        let doc = f.doc();
+        if let Some(domain) = doc.domain {
+            lines.push(format!("  {domain}"));
+        }
+        if !doc.examples.is_empty() {
+            lines.push("Examples:".to_string());
+            // a fresh calculator, so the examples don't touch the session or its results
+            let mut scratch = Calculator::new();
+            for example in doc.examples {
+                lines.push(match scratch.eval(example) {
+                    Ok(v) => format!("  {example}  = {}", disp_value(&v, HELP_DIGITS)),
+                    Err(_) => format!("  {example}"),
+                });
+            }
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics() {
+        let mut calc = Calculator::new();
+        calc.eval("hyp(a, b) = sqrt(a^2 + b^2)  # hypotenuse")
+            .unwrap();
+        assert_eq!(help(Some("hyp"), &calc).unwrap(), "hyp(a, b)\n  hypotenuse");
+        let sqrt = help(Some("sqrt"), &calc).unwrap();
+        assert!(sqrt.starts_with("sqrt(x)\n  Square root\n"), "{sqrt}");
+        assert!(sqrt.contains("√16  = 4"), "{sqrt}");
+        assert!(help(None, &calc).unwrap().contains("hyp, map"));
+        assert!(help(Some("nope"), &calc).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::session::data_file;
+
+/// Entries past this are dropped from the front.
+const HISTORY_CAP: usize = 1000;
+
+pub struct Entry {
+    pub input: String,
+    /// The displayed result, `None` for commands.
+    pub result: Option<String>,
+}
+
+/// Input history, kept in `history` in the data dir with one `input\tresult` entry per line.
+/// Line breaks in multi-line inputs are written as `\n`.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Reads the history file, starting out empty if there is none.
+    pub fn load() -> Self {
+        let path = data_file("history").ok();
+        let entries = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|s| parse(&s))
+            .unwrap_or_default();
+        Self { entries, path }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|e| e.input.as_str())
+    }
+
+    /// Index of the newest entry before `before` containing `query`.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.len())]
+            .iter()
+            .rposition(|e| e.input.contains(query))
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// Adds an entry unless it repeats the last one and writes the file.
+    pub fn push(&mut self, input: String, result: Option<String>) -> io::Result<()> {
+        match self.entries.last_mut() {
+            Some(last) if last.input == input => last.result = result,
+            _ => self.entries.push(Entry { input, result }),
+        }
+        if self.entries.len() > HISTORY_CAP {
+            self.entries.drain(..self.entries.len() - HISTORY_CAP);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = String::new();
+        for e in &self.entries {
+            out += &e.input.replace('\\', "\\\\").replace('\n', "\\n");
+            if let Some(r) = &e.result {
+                out += "\t";
+                out += r;
+            }
+            out += "\n";
+        }
+        fs::write(path, out)
+    }
+}
+
+fn parse(source: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = source
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| match l.split_once('\t') {
+            Some((input, result)) => Entry {
+                input: unescape(input),
+                result: Some(result.to_string()),
+            },
+            None => Entry {
+                input: unescape(l),
+                result: None,
+            },
+        })
+        .collect();
+    entries.dedup_by(|a, b| a.input == b.input);
+    if entries.len() > HISTORY_CAP {
+        entries.drain(..entries.len() - HISTORY_CAP);
+    }
+    entries
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedups_and_caps() {
+        let mut history = History::default();
+        history.push("1+1".into(), Some("2".into())).unwrap();
+        history.push("1+1".into(), Some("2".into())).unwrap();
+        history.push(":save".into(), None).unwrap();
+        history.push("1+1".into(), Some("2".into())).unwrap();
+        assert_eq!(history.len(), 3);
+
+        for i in 0..HISTORY_CAP {
+            history.push(i.to_string(), None).unwrap();
+        }
+        assert_eq!(history.len(), HISTORY_CAP);
+        assert_eq!(history.get(0), Some("0"));
+    }
+
+    #[test]
+    fn searches_backwards() {
+        let mut history = History::default();
+        for input in ["sqrt(2)", "x = 3", "sqrt(x)", "1+1"] {
+            history.push(input.into(), None).unwrap();
+        }
+        assert_eq!(history.search("sqrt", history.len()), Some(2));
+        assert_eq!(history.search("sqrt", 2), Some(0));
+        assert_eq!(history.search("sqrt", 0), None);
+        assert_eq!(history.search("y", history.len()), None);
+    }
+
+    #[test]
+    fn parses_file() {
+        let entries = parse("x = 2\t2\n:history\n:history\n\nx^2\t4\nf(x) = (\\n  x)\\\\n\n");
+        let inputs: Vec<_> = entries.iter().map(|e| e.input.as_str()).collect();
+        assert_eq!(inputs, ["x = 2", ":history", "x^2", "f(x) = (\n  x)\\n"]);
+        assert_eq!(entries[2].result.as_deref(), Some("4"));
+        assert_eq!(entries[1].result, None);
+    }
+}
@@ -0,0 +1,113 @@
+use crossterm::event::KeyCode;
+use ferricalc::{format_num, Calculator, EnvMember, Format, Func};
+
+pub struct Entry {
+    pub name: String,
+    /// `x = 1.5`, the definition of a user function, or the signature of a builtin one.
+    pub text: String,
+    pub builtin: bool,
+}
+
+/// The sidebar listing everything defined, user definitions first.
+pub struct Inspector {
+    pub entries: Vec<Entry>,
+    pub selected: usize,
+    /// Whether keys go to the list rather than the input.
+    pub focused: bool,
+    /// A fresh session, to tell builtin constants from ones the user has changed.
+    builtins: Calculator,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            selected: 0,
+            focused: false,
+            builtins: Calculator::new(),
+        }
+    }
+}
+
+impl Inspector {
+    /// Lists what `calc` defines now, keeping the same name selected.
+    pub fn refresh(&mut self, calc: &Calculator, format: &Format) {
+        let selected = self.entries.get(self.selected).map(|e| e.name.clone());
+        self.entries = calc
+            .env()
+            .iter()
+            .map(|(name, m)| {
+                let (text, builtin) = match m {
+                    EnvMember::Var(v) => (
+                        format!("{name} = {}", format_num(v, format)),
+                        self.builtins.env().get_var(name) == Some(v),
+                    ),
+                    EnvMember::Fn(f @ Func::UserFn(_)) => (
+                        m.definition(name)
+                            .unwrap_or_else(|| format!("{name}({})", f.params())),
+                        false,
+                    ),
+                    EnvMember::Fn(f) => (format!("{name}({})", f.params()), true),
+                };
+                Entry {
+                    name: name.to_string(),
+                    text,
+                    builtin,
+                }
+            })
+            .collect();
+        self.entries.sort_by_key(|e| e.builtin);
+        self.selected = selected
+            .and_then(|name| self.entries.iter().position(|e| e.name == name))
+            .unwrap_or(0);
+    }
+
+    /// Moves through the list while it has focus. Gives the selected name when it is chosen.
+    pub fn handle_key(&mut self, code: KeyCode) -> Option<String> {
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1))
+            }
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = self.entries.len().saturating_sub(1),
+            KeyCode::Enter => {
+                self.focused = false;
+                return self.entries.get(self.selected).map(|e| e.name.clone());
+            }
+            KeyCode::Tab | KeyCode::Esc => self.focused = false,
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_user_definitions_first() {
+        let mut calc = Calculator::new();
+        calc.eval("x = 1.5").unwrap();
+        calc.eval("f(y) = x * y").unwrap();
+        calc.eval("e = 3").unwrap();
+        let mut insp = Inspector::default();
+        let format = Format {
+            digits: 16,
+            ..Format::default()
+        };
+        insp.refresh(&calc, &format);
+        let texts: Vec<_> = insp.entries.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts[..3], ["e = 3", "f(y) = x * y", "x = 1.5"]);
+        assert!(texts.contains(&"sqrt(x)"));
+        assert!(texts.contains(&"pi = 3.141592653589793"));
+
+        insp.focused = true;
+        insp.handle_key(KeyCode::Down);
+        calc.eval("a = 1").unwrap();
+        insp.refresh(&calc, &format);
+        assert_eq!(insp.handle_key(KeyCode::Enter).as_deref(), Some("f"));
+        assert!(!insp.focused);
+    }
+}
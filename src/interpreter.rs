@@ -1,10 +1,20 @@
 pub mod ast;
 pub mod env;
+pub mod error;
+pub mod free_vars;
+pub mod functional;
 pub mod math;
+pub mod native;
 pub mod parser;
+pub mod printer;
+pub mod value;
 
 use self::ast::*;
 use self::env::*;
+use self::error::Error;
+use self::free_vars::FreeVars;
+use self::native::{Arity, NativeFn};
+use self::value::*;
 
 use crate::scanner::TokenKind;
 use rug::{ops::Pow, Float};
@@ -14,117 +24,477 @@ pub const PREC_BITS: u32 = 256;
 
 pub struct Interpreter {
     pub env: Env,
-    scope: Option<HashMap<String, Float>>,
+    scope: Option<HashMap<String, Value>>,
+    /// Assignments made while `save_assignments` is off, seen as globals until the program ends.
+    pending: HashMap<String, Value>,
+    /// How many user functions deep the evaluation is. Their bodies come from other input, so
+    /// errors in them are located at the outermost call.
+    depth: usize,
+    /// Where in the program the current statement failed, if that is more precise than its
+    /// start.
+    error_offset: Option<usize>,
 
-    pub last_ans: Float,
+    pub last_ans: Value,
+    /// Every evaluated program's result, referred to as `$1`, `ans1`, `_1` or `ans(1)`.
+    pub results: Vec<Value>,
     pub save_assignments: bool,
 }
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Interpreter {
     pub fn new() -> Self {
         let mut env = Env::new();
+        math::insert_consts(&mut env);
         math::insert_funcs(&mut env);
+        functional::insert_funcs(&mut env);
+        env.register(
+            NativeFn::new(
+                "ans",
+                Arity::Exact(1),
+                "The nth result, like $n",
+                |ctx, args| {
+                    let n = args[0].clone().into_number()?;
+                    ctx.get(&format!("${}", n.to_f64()))
+                        .ok_or_else(|| format!("No result number {}", n.to_f64()).into())
+                },
+            )
+            .with_params("n"),
+        );
         Self {
             env,
             scope: None,
-            last_ans: Float::new(PREC_BITS),
+            pending: HashMap::new(),
+            depth: 0,
+            error_offset: None,
+            last_ans: Value::Number(Float::new(PREC_BITS)),
+            results: Vec::new(),
             save_assignments: true,
         }
     }
+
+    fn visit_number(&mut self, e: &Expr) -> Result<Float, String> {
+        self.visit_expr(e)?.into_number()
+    }
+
+    /// Runs each statement in turn and returns the value of the last one, updating `ans` after
+    /// each. When `save_assignments` is off, assignments and `ans` are only visible to the rest
+    /// of the program.
+    pub fn run_program(&mut self, program: Program) -> Result<Value, Error> {
+        let last_ans = self.last_ans.clone();
+        let mut res = Err(Error {
+            message: "Expected expression".to_string(),
+            offset: 0,
+        });
+        for (stmt, offset) in program.stmts.into_iter().zip(program.offsets) {
+            self.error_offset = None;
+            res = self.visit_stmt_owned(stmt).map_err(|message| Error {
+                message,
+                offset: self.error_offset.unwrap_or(offset),
+            });
+            match &res {
+                Ok(v) => self.last_ans = v.clone(),
+                Err(_) => break,
+            }
+        }
+        self.scope = None;
+        self.pending.clear();
+        if !self.save_assignments {
+            self.last_ans = last_ans;
+        } else if let Ok(v) = &res {
+            self.results.push(v.clone());
+        }
+        res
+    }
+
+    /// Assigns the global `name`, or only for the rest of the program when `save_assignments`
+    /// is off. Either way functions called later see it the same.
+    fn assign(&mut self, name: String, value: Value) -> Result<(), String> {
+        match value {
+            Value::List(_) => Err(format!("Cannot assign a list to '{name}'")),
+            value if !self.save_assignments => {
+                self.pending.insert(name, value);
+                Ok(())
+            }
+            Value::Number(n) => self.env.set_var(name, n),
+            Value::Fn(f) => self.env.set_func(name, f),
+        }
+    }
+
+    /// Binds `name` in the innermost scope, creating a top level one if needed.
+    fn bind_local(&mut self, name: String, value: Value) {
+        self.scope
+            .get_or_insert_with(HashMap::new)
+            .insert(name, value);
+    }
+
+    /// Snapshots the globals `f` refers to into its captures, so that redefining them later
+    /// doesn't change `f`. Builtins are still resolved when `f` is called.
+    fn capture_globals(&self, f: &mut UserFn) -> Result<(), String> {
+        let free = FreeVars::of(&f.arguments, &f.expr);
+        for name in &free.vars {
+            if f.captures.contains_key(name) {
+                continue;
+            }
+            let value = self
+                .global(name)
+                .ok_or(format!("Cannot capture undeclared variable '{name}'"))?;
+            f.captures.insert(name.clone(), value);
+        }
+        for name in &free.funcs {
+            match self.global_func(name) {
+                _ if f.captures.contains_key(name) => {}
+                Some(g @ Func::UserFn(_)) => {
+                    f.captures.insert(name.clone(), Value::Fn(g.clone()));
+                }
+                Some(_) => {}
+                None => return Err(format!("No function named '{name}'")),
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.scope
+            .as_ref()
+            .and_then(|s| s.get(name).cloned())
+            .or_else(|| self.global(name))
+    }
+
+    fn global(&self, name: &str) -> Option<Value> {
+        (name == "ans")
+            .then(|| self.last_ans.clone())
+            .or_else(|| self.pending.get(name).cloned())
+            .or_else(|| self.env.get_var(name).cloned().map(Value::Number))
+            .or_else(|| self.result(name))
+            .or_else(|| self.env.get_func(name).cloned().map(Value::Fn))
+    }
+
+    fn global_func(&self, name: &str) -> Option<Func> {
+        match self.pending.get(name) {
+            Some(Value::Fn(f)) => Some(f.clone()),
+            // a pending variable replaces a function of the same name
+            Some(_) => None,
+            None => self.env.get_func(name).cloned(),
+        }
+    }
+
+    /// Notes `offset` as where the statement failed, unless an inner expression already did.
+    fn locate<T>(&mut self, res: Result<T, String>, offset: usize) -> Result<T, String> {
+        if res.is_err() && self.depth == 0 && self.error_offset.is_none() {
+            self.error_offset = Some(offset);
+        }
+        res
+    }
+
+    /// Resolves `$n`, `ansn` and `_n`, counting from 1.
+    fn result(&self, name: &str) -> Option<Value> {
+        let n = ["$", "ans", "_"]
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))?;
+        if n.starts_with('0') {
+            return None;
+        }
+        let n: usize = n.parse().ok()?;
+        self.results.get(n.checked_sub(1)?).cloned()
+    }
+
+    /// Calls `f` with already evaluated arguments. `name` is only used in error messages.
+    pub fn call(&mut self, name: &str, f: &Func, args: Vec<Value>) -> Result<Value, String> {
+        match f {
+            Func::BuiltinFn(f) => {
+                let args: Vec<Float> = flatten(args)
+                    .into_iter()
+                    .map(Value::into_number)
+                    .collect::<Result<_, _>>()?;
+                if args.is_empty() {
+                    return Err(format!("Function '{name}' takes at least 1 arg"));
+                }
+                Ok(Value::Number((f.func)(&args)))
+            }
+            Func::NativeFn(f) => f.call(self, &args),
+            Func::UserFn(f) => {
+                if f.arguments.len() != args.len() {
+                    return Err(format!(
+                        "Function '{name}' takes {} args",
+                        f.arguments.len()
+                    ));
+                }
+                let mut scope = f.captures.clone();
+                scope.extend(f.arguments.iter().cloned().zip(args));
+                let outer = self.scope.replace(scope);
+                self.depth += 1;
+                let res = self.visit_expr(&Rc::clone(&f.expr));
+                self.depth -= 1;
+                self.scope = outer;
+                res
+            }
+        }
+    }
 }
 
-impl Visitor<Result<Float, String>> for Interpreter {
-    fn visit_grouping_expr(&mut self, e: &GroupingExpr) -> Result<Float, String> {
+impl Visitor<Result<Value, String>> for Interpreter {
+    fn visit_grouping_expr(&mut self, e: &GroupingExpr) -> Result<Value, String> {
         self.visit_expr(&e.0)
     }
-    fn visit_var(&mut self, e: &Var) -> Result<Float, String> {
+    fn visit_var(&mut self, e: &Var) -> Result<Value, String> {
         let name = e.name.as_str();
-        (name == "ans")
-            .then_some(self.last_ans.clone())
-            .or(self.scope.as_mut().and_then(|s| s.get(name).cloned()))
-            .or(self.env.get_var(name).cloned())
-            .ok_or(format!("Undeclared variable '{name}'"))
-    }
-    fn visit_binary_expr(&mut self, e: &BinaryExpr) -> Result<Float, String> {
-        let lhs = self.visit_expr(&e.lhs)?;
-        let rhs = self.visit_expr(&e.rhs)?;
-        Ok(match e.operator.kind {
+        let res = self
+            .lookup(name)
+            .ok_or(format!("Undeclared variable '{name}'"));
+        self.locate(res, e.offset)
+    }
+    fn visit_binary_expr(&mut self, e: &BinaryExpr) -> Result<Value, String> {
+        let lhs = self.visit_number(&e.lhs);
+        let lhs = self.locate(lhs, e.operator.start)?;
+        let rhs = self.visit_number(&e.rhs);
+        let rhs = self.locate(rhs, e.operator.start)?;
+        Ok(Value::Number(match e.operator.kind {
             TokenKind::Plus => lhs + rhs,
             TokenKind::Minus => lhs - rhs,
             TokenKind::Slash => lhs / rhs,
             TokenKind::Star => lhs * rhs,
             TokenKind::Exp => lhs.pow(&rhs),
             ref t => panic!("Unexpected Token {t:?}"),
-        })
+        }))
     }
 
-    fn visit_unary_expr(&mut self, e: &UnaryExpr) -> Result<Float, String> {
-        let rhs = self.visit_expr(&e.rhs)?;
-        Ok(match e.operator.kind {
+    fn visit_unary_expr(&mut self, e: &UnaryExpr) -> Result<Value, String> {
+        let rhs = self.visit_number(&e.rhs)?;
+        Ok(Value::Number(match e.operator.kind {
             TokenKind::Minus => -rhs,
             TokenKind::Plus => rhs,
+            TokenKind::Sqrt => rhs.sqrt(),
             ref t => panic!("Unexpected Token {t:?}"),
-        })
+        }))
     }
 
-    fn visit_literal(&mut self, e: &Literal) -> Result<Float, String> {
+    fn visit_literal(&mut self, e: &Literal) -> Result<Value, String> {
         Ok(match e {
-            Literal::Number(n) => n.clone(),
+            Literal::Number(n) => Value::Number(n.clone()),
         })
     }
 
-    fn visit_func_call(&mut self, e: &FnCall) -> Result<Float, String> {
+    fn visit_func_call(&mut self, e: &FnCall) -> Result<Value, String> {
         let name = e.name.as_str();
-        let args: Vec<Float> = e
+        let args: Vec<Value> = e
             .arguments
             .iter()
             .map(|e| self.visit_expr(e))
             .collect::<Result<_, _>>()?;
-        match self
-            .env
-            .get_func(name)
-            .ok_or(format!("No function named '{name}'"))?
-        {
-            Func::BuiltinFn(f) => Ok(f(&args)),
-            Func::UserFn(f) => {
-                if !(f.arguments.len() == e.arguments.len()) {
-                    return Err(format!(
-                        "Function '{name}' takes {} args",
-                        f.arguments.len()
-                    ));
-                }
-                let f_args = f.arguments.clone();
-                self.scope = Some(f_args.into_iter().zip(args).collect());
-                let res = self.visit_expr(&Rc::clone(&f.expr));
-                self.scope = None;
-                res
+        let f = match self.scope.as_ref().and_then(|s| s.get(name)) {
+            Some(v) => v.clone().into_func(),
+            None => self
+                .global_func(name)
+                .ok_or(format!("No function named '{name}'")),
+        };
+        let res = f.and_then(|f| self.call(name, &f, args));
+        self.locate(res, e.offset)
+    }
+    fn visit_lambda(&mut self, e: &Lambda) -> Result<Value, String> {
+        Ok(Value::Fn(Func::UserFn(UserFn {
+            expr: Rc::clone(&e.expr),
+            arguments: e.arguments.clone(),
+            captures: self.scope.clone().unwrap_or_default(),
+        })))
+    }
+    fn visit_let(&mut self, e: &LetExpr) -> Result<Value, String> {
+        let outer = self.scope.clone();
+        let mut res = Ok(Value::Number(Float::new(PREC_BITS)));
+        for (name, value) in &e.bindings {
+            res = self.visit_expr(value);
+            match &res {
+                Ok(v) => self.bind_local(name.clone(), v.clone()),
+                Err(_) => break,
             }
         }
+        if res.is_ok() {
+            res = self.visit_expr(&e.body);
+        }
+        self.scope = outer;
+        res
     }
-    fn visit_stmt_owned(&mut self, s: Stmt) -> Result<Float, String> {
+    fn visit_stmt_owned(&mut self, s: Stmt) -> Result<Value, String> {
         match s {
             Stmt::VarAssign(e) => {
-                let res = self.visit_expr(&e.value)?;
-                if self.save_assignments {
-                    self.env.set_var(e.name, res.clone())?;
+                let mut res = self.visit_expr(&e.value)?;
+                if let (true, Value::Fn(Func::UserFn(f))) = (e.capture, &mut res) {
+                    self.capture_globals(f)?;
                 }
+                self.assign(e.name, res.clone())?;
                 Ok(res)
             }
             Stmt::FnAssign(e) => {
-                if self.save_assignments {
-                    self.env.set_func(
-                        e.name,
-                        Func::UserFn(UserFn {
-                            expr: Rc::new(e.expr),
-                            arguments: e.arguments,
-                        }),
-                    )?;
+                let mut f = UserFn {
+                    expr: Rc::new(e.expr),
+                    arguments: e.arguments,
+                    captures: HashMap::new(),
+                };
+                if e.capture {
+                    self.capture_globals(&mut f)?;
                 }
-                Ok(Float::with_val(PREC_BITS, 1.0))
+                let f = Value::Fn(Func::UserFn(f));
+                self.assign(e.name, f.clone())?;
+                Ok(f)
             }
             Stmt::Expr(e) => self.visit_expr(&e),
         }
     }
-    fn visit_stmt(&mut self, _s: &Stmt) -> Result<Float, String> {
+    fn visit_stmt(&mut self, _s: &Stmt) -> Result<Value, String> {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::parser::Parser, scanner::Scanner, util::disp_value};
+
+    fn eval(interp: &mut Interpreter, src: &str) -> Result<String, String> {
+        let tokens = Scanner::new(src).scan_tokens()?;
+        let program = Parser::new(&tokens, src).parse().map_err(|e| e.message)?;
+        interp
+            .run_program(program)
+            .map(|v| disp_value(&v, 16))
+            .map_err(|e| e.message)
+    }
+
+    #[test]
+    fn lambdas() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "sq = x -> x^2").unwrap();
+        assert_eq!(eval(&mut i, "sq(3)").unwrap(), "9");
+        eval(&mut i, "mul = (a, b) -> a*b").unwrap();
+        assert_eq!(eval(&mut i, "mul(3, 4)").unwrap(), "12");
+        eval(&mut i, "adder(k) = x -> x + k").unwrap();
+        eval(&mut i, "add2 = adder(2)").unwrap();
+        assert_eq!(eval(&mut i, "add2(5)").unwrap(), "7");
+        assert!(eval(&mut i, "mul(1)").is_err());
+    }
+
+    #[test]
+    fn higher_order_builtins() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "f(x) = x*2").unwrap();
+        assert_eq!(eval(&mut i, "map(x -> x^2, 1, 2, 3)").unwrap(), "[1, 4, 9]");
+        assert_eq!(eval(&mut i, "map(f, 1, 2)").unwrap(), "[2, 4]");
+        assert_eq!(eval(&mut i, "sum(map(sqrt, 4, 9))").unwrap(), "5");
+        assert_eq!(
+            eval(&mut i, "filter(x -> x - 2, 1, 2, 3)").unwrap(),
+            "[1, 3]"
+        );
+        assert_eq!(
+            eval(&mut i, "reduce((a, b) -> a*b, 1, 2, 3, 4)").unwrap(),
+            "24"
+        );
+        assert_eq!(eval(&mut i, "apply(max, 1, 5, 2)").unwrap(), "5");
+        eval(&mut i, "h = compose(f, x -> x + 1)").unwrap();
+        assert_eq!(eval(&mut i, "h(3)").unwrap(), "8");
+        assert!(eval(&mut i, "map(1, 2)").is_err());
+    }
+
+    #[test]
+    fn capture_mode() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "k = 2").unwrap();
+        assert_eq!(eval(&mut i, "f(x) = k*x").unwrap(), "<fn(x) uses k>");
+        assert_eq!(eval(&mut i, "g(x) := k*x").unwrap(), "<fn(x) captures k>");
+        eval(&mut i, "h := x -> k + x").unwrap();
+        eval(&mut i, "k = 3").unwrap();
+        assert_eq!(eval(&mut i, "f(1)").unwrap(), "3");
+        assert_eq!(eval(&mut i, "g(1)").unwrap(), "2");
+        assert_eq!(eval(&mut i, "h(1)").unwrap(), "3");
+
+        eval(&mut i, "inc(x) = x + 1").unwrap();
+        eval(&mut i, "twice(x) := inc(inc(x))").unwrap();
+        eval(&mut i, "inc(x) = x").unwrap();
+        assert_eq!(eval(&mut i, "twice(1)").unwrap(), "3");
+        assert!(eval(&mut i, "bad(x) := x + unknown").is_err());
+    }
+
+    #[test]
+    fn programs_and_let() {
+        let mut i = Interpreter::new();
+        assert_eq!(eval(&mut i, "a = 3; b = 4; sqrt(a^2 + b^2)").unwrap(), "5");
+        assert_eq!(eval(&mut i, "a = 1;").unwrap(), "1");
+        assert_eq!(eval(&mut i, "let r = 2 in r^3").unwrap(), "8");
+        assert_eq!(eval(&mut i, "let a = 2, b = a + 1 in a*b").unwrap(), "6");
+        assert_eq!(eval(&mut i, "(let x = 5 in x) + a").unwrap(), "6");
+        assert!(eval(&mut i, "let x = 1 in y").is_err());
+        assert!(eval(&mut i, "x").is_err());
+
+        i.save_assignments = false;
+        assert_eq!(eval(&mut i, "c = 10; c + a").unwrap(), "11");
+        assert!(eval(&mut i, "c").is_err());
+    }
+
+    #[test]
+    fn comments() {
+        let mut i = Interpreter::new();
+        assert_eq!(eval(&mut i, "1 + 2 # three").unwrap(), "3");
+        assert_eq!(eval(&mut i, "6 / 2 // half of six").unwrap(), "3");
+        assert_eq!(eval(&mut i, "a = 1; # a = 2").unwrap(), "1");
+        assert!(eval(&mut i, "# nothing").is_err());
+    }
+
+    #[test]
+    fn nested_user_fn_calls() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "f(x) = x").unwrap();
+        eval(&mut i, "g(x) = f(x + 1) + x").unwrap();
+        assert_eq!(eval(&mut i, "g(1)").unwrap(), "3");
+    }
+
+    #[test]
+    fn newlines() {
+        let mut i = Interpreter::new();
+        assert_eq!(eval(&mut i, "x = 2 # two\n\ny = x +\n  1\n").unwrap(), "3");
+        assert_eq!(eval(&mut i, "f(a) = (\n  a *\n  y\n)\nf(x)").unwrap(), "6");
+        assert!(eval(&mut i, "(1\n2)").is_err());
+    }
+
+    #[test]
+    fn unicode() {
+        let mut i = Interpreter::new();
+        assert_eq!(eval(&mut i, "2×3÷4·2").unwrap(), "3");
+        assert_eq!(eval(&mut i, "√16 + 3² − 2³").unwrap(), "5");
+        assert_eq!(eval(&mut i, "2⁻¹ + 10¹⁰").unwrap(), "10000000000.5");
+        assert_eq!(eval(&mut i, "π - pi").unwrap(), "0");
+        assert_eq!(eval(&mut i, "θ = 3; µ_2 = θ²; µ_2").unwrap(), "9");
+        assert!(eval(&mut i, "2⁻").is_err());
+    }
+
+    #[test]
+    fn result_refs() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "2 + 3").unwrap();
+        eval(&mut i, "x = 10").unwrap();
+        i.save_assignments = false;
+        eval(&mut i, "100").unwrap();
+        i.save_assignments = true;
+        assert_eq!(eval(&mut i, "$1 * $2").unwrap(), "50");
+        assert_eq!(eval(&mut i, "ans(3) + ans1 + _2").unwrap(), "65");
+        assert_eq!(i.results.len(), 4);
+        assert!(eval(&mut i, "$9").is_err());
+        assert!(eval(&mut i, "ans(0)").is_err());
+        eval(&mut i, "_1 = 7").unwrap();
+        assert_eq!(eval(&mut i, "_1").unwrap(), "7");
+    }
+
+    #[test]
+    fn signatures() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "hypot(x, y) = sqrt(x^2 + y^2)").unwrap();
+        let params = |i: &Interpreter, name| i.env.get_func(name).unwrap().params();
+        assert_eq!(params(&i, "hypot"), "x, y");
+        assert_eq!(params(&i, "sum"), "x, ...");
+        assert_eq!(params(&i, "reduce"), "f, x, y, ...");
+        assert_eq!(i.env.help("hypot"), None);
+        assert_eq!(i.env.help("sqrt"), Some("Square root"));
+        assert!(i.env.help("pi").is_some());
+        eval(&mut i, "e = 3").unwrap();
+        assert_eq!(i.env.help("e"), None);
+    }
+}
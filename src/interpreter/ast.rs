@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, rc::Rc};
 
 use rug::Float;
 
@@ -12,12 +12,20 @@ pub enum Expr {
     Grouping(Box<GroupingExpr>),
     Var(Var),
     FnCall(FnCall),
+    Lambda(Lambda),
+    Let(Box<LetExpr>),
 }
 pub enum Stmt {
     VarAssign(VarAssign),
     FnAssign(FnAssign),
     Expr(Expr),
 }
+/// The `;`-separated statements of one input line.
+pub struct Program {
+    pub stmts: Vec<Stmt>,
+    /// Byte offset in the source where each statement starts.
+    pub offsets: Vec<usize>,
+}
 
 
 
@@ -25,22 +33,40 @@ pub enum Stmt {
 pub struct VarAssign {
     pub name: String,
     pub value: Expr,
+    /// Set for `:=`, which snapshots the globals a function value refers to.
+    pub capture: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct Var {
     pub name: String,
+    /// Byte offset of the name in the source, for locating errors.
+    pub offset: usize,
 }
 #[derive(Debug, Clone)]
 pub struct FnCall {
     pub name: String,
     pub arguments: Vec<Expr>,
+    /// Byte offset of the name in the source, for locating errors.
+    pub offset: usize,
+}
+#[derive(Clone, Debug)]
+pub struct Lambda {
+    pub arguments: Vec<String>,
+    pub expr: Rc<Expr>,
+}
+/// `let a = 1, b = a + 1 in a * b`. Each binding can see the ones before it.
+#[derive(Clone, Debug)]
+pub struct LetExpr {
+    pub bindings: Vec<(String, Expr)>,
+    pub body: Expr,
 }
 #[derive(Clone, Debug)]
 pub struct FnAssign {
     pub name: String,
     pub arguments: Vec<String>,
     pub expr: Expr,
+    pub capture: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -83,6 +109,8 @@ pub trait Visitor<T> {
     fn visit_literal(&mut self, e: &Literal) -> T;
     fn visit_var(&mut self, e: &Var) -> T;
     fn visit_func_call(&mut self, e: &FnCall) -> T;
+    fn visit_lambda(&mut self, e: &Lambda) -> T;
+    fn visit_let(&mut self, e: &LetExpr) -> T;
     fn visit_expr(&mut self, e: &Expr) -> T {
         match e {
             Expr::Literal(e) => self.visit_literal(e),
@@ -91,6 +119,8 @@ pub trait Visitor<T> {
             Expr::Grouping(e) => self.visit_grouping_expr(e),
             Expr::Var(e) => self.visit_var(e),
             Expr::FnCall(e) => self.visit_func_call(e),
+            Expr::Lambda(e) => self.visit_lambda(e),
+            Expr::Let(e) => self.visit_let(e),
         }
     }
     fn visit_stmt_owned(&mut self, s: Stmt) -> T {
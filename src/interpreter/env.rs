@@ -1,23 +1,75 @@
 use std::{
     borrow::{Borrow, Cow},
-    collections::{btree_map::Entry, BTreeMap},
+    collections::{BTreeMap, HashMap},
     rc::Rc,
 };
 
 use rug::Float;
 
-use crate::ast::Expr;
+use super::{native::NativeFn, value::Value};
+use super::ast::Expr;
 
 #[derive(Debug, Clone)]
 pub struct UserFn {
     pub expr: Rc<Expr>,
     pub arguments: Vec<String>,
+    /// Locals visible where the function was created, e.g. the parameters of an enclosing
+    /// function for `adder(k) = x -> x + k`.
+    pub captures: HashMap<String, Value>,
 }
 
+/// More about a builtin function than its one-line help, for `:help`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Doc {
+    /// Inputs that show off the function, without their results.
+    pub examples: &'static [&'static str],
+    /// Which arguments the function is defined for, and what happens outside of that.
+    pub domain: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BuiltinFn {
+    pub name: &'static str,
+    pub params: &'static str,
+    pub help: &'static str,
+    pub doc: Doc,
+    pub func: fn(&[Float]) -> Float,
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum Func {
     UserFn(UserFn),
-    BuiltinFn(fn(&[Float]) -> Float),
+    BuiltinFn(BuiltinFn),
+    NativeFn(Rc<NativeFn>),
+}
+
+impl Func {
+    /// The parameter list, as in `hypot(x, y)`.
+    pub fn params(&self) -> String {
+        match self {
+            Func::UserFn(f) => f.arguments.join(", "),
+            Func::BuiltinFn(f) => f.params.to_string(),
+            Func::NativeFn(f) => f.params.clone(),
+        }
+    }
+
+    /// The built-in description, if there is one.
+    pub fn help(&self) -> Option<&str> {
+        match self {
+            Func::UserFn(_) => None,
+            Func::BuiltinFn(f) => Some(f.help),
+            Func::NativeFn(f) => Some(f.help.as_str()).filter(|h| !h.is_empty()),
+        }
+    }
+
+    pub fn doc(&self) -> Doc {
+        match self {
+            Func::UserFn(_) => Doc::default(),
+            Func::BuiltinFn(f) => f.doc,
+            Func::NativeFn(f) => f.doc,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -43,51 +95,83 @@ pub enum EnvMemberKind {
 
 
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Env {
     pub(super) members: BTreeMap<Cow<'static, str>, EnvMember>,
+    /// Descriptions of members, which are dropped when a member is redefined.
+    docs: HashMap<String, String>,
 }
 impl<'q, 'env: 'q> Env {
     pub fn new() -> Self {
         Self {
             members: <_>::default(),
+            docs: <_>::default(),
         }
     }
+    pub fn set_doc(&mut self, name: &str, doc: &str) {
+        self.docs.insert(name.to_string(), doc.to_string());
+    }
+    /// The description given with [`Env::set_doc`], e.g. a user function's docstring.
+    pub fn doc(&self, name: &str) -> Option<&str> {
+        self.docs.get(name).map(String::as_str)
+    }
+    /// The description of `name`, either given with [`Env::set_doc`] or built into a function.
+    pub fn help<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.docs
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| match self.members.get(name)? {
+                EnvMember::Fn(f) => f.help(),
+                EnvMember::Var(_) => None,
+            })
+    }
+    pub fn register(&mut self, f: NativeFn) {
+        self.members
+            .insert(f.name.clone().into(), EnvMember::Fn(Func::NativeFn(Rc::new(f))));
+    }
     pub fn set_func(&mut self, k: String, v: Func) -> Result<(), String> {
-        match self.members.entry(k.into()) {
-            Entry::Occupied(mut e) => {e.insert(EnvMember::Fn(v));},
-            Entry::Vacant(e) => {e.insert(EnvMember::Fn(v));}
-        }
+        self.docs.remove(&k);
+        self.members.insert(k.into(), EnvMember::Fn(v));
         Ok(())
     }
+    /// Removes `name` and its docstring, giving back what it was.
+    pub fn remove(&mut self, name: &str) -> Option<EnvMember> {
+        self.docs.remove(name);
+        self.members.remove(name)
+    }
     pub fn get_func(&'env self, q: &'q str) -> Option<&'q Func> {
-        self.members.get(q.into()).and_then(|e| match e {
+        self.members.get(q).and_then(|e| match e {
             EnvMember::Fn(v) => Some(v),
             _ => None,
         })
     }
 
     pub fn set_var<K: Into<Cow<'static, str>>>(&mut self, k: K, v: Float) -> Result<(), String> {
-        self.members
-            .entry(k.into())
-            .and_modify(|t| match t {
-                EnvMember::Var(f) => *f = v.clone(),
-                _ => (),
-            })
-            .or_insert(EnvMember::Var(v));
+        let k = k.into();
+        self.docs.remove(k.as_ref());
+        self.members.insert(k, EnvMember::Var(v));
         Ok(())
     }
     pub fn get_var(&'env self, q: &'q str) -> Option<&'q Float> {
-        self.members.get(q.into()).and_then(|e| match e {
+        self.members.get(q).and_then(|e| match e {
             EnvMember::Var(v) => Some(v),
             _ => None,
         })
     }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &EnvMember)> {
+        self.members.iter().map(|(k, e)| ((*k).borrow(), e))
+    }
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &Float)> {
+        self.members.iter().filter_map(|(k, e)| match e {
+            EnvMember::Var(v) => Some(((*k).borrow(), v)),
+            EnvMember::Fn(_) => None,
+        })
+    }
     pub fn search(&'env self, q: &'q str) -> impl Iterator<Item = (&'q str, &'q EnvMember)> {
         let r = <Cow<_>>::from(q);
         self.members
             .range(r..)
-            .take_while(move |(k, _)| k.len() >= q.len() && q == &k[0..q.len()])
+            .take_while(move |(k, _)| k.starts_with(q))
             .map(|(k, e)| ((*k).borrow(), e))
     }
 }
@@ -104,5 +188,8 @@ mod tests {
         for (s, _) in e.members.range(<Cow<_>>::from("sq")..) {
             println!("{s}")
         }
+        e.set_var("π", Float::new(1)).unwrap();
+        let found: Vec<_> = e.search("s").map(|(name, _)| name).collect();
+        assert_eq!(found, ["sin", "sqrt", "sum"]);
     }
 }
@@ -0,0 +1,29 @@
+use std::fmt::Display;
+
+/// An error along with the byte offset in the source where it was detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub message: String,
+    pub offset: usize,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Errors raised while evaluating, whose position is filled in by the statement they come from.
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self { message, offset: 0 }
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
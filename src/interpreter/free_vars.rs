@@ -0,0 +1,79 @@
+use std::collections::BTreeSet;
+
+use super::ast::*;
+
+/// Collects the names an expression refers to that aren't bound by one of its own parameters.
+#[derive(Default)]
+pub struct FreeVars {
+    bound: Vec<String>,
+    pub vars: BTreeSet<String>,
+    pub funcs: BTreeSet<String>,
+}
+
+impl FreeVars {
+    pub fn of(arguments: &[String], e: &Expr) -> Self {
+        let mut free = Self {
+            bound: arguments.to_vec(),
+            ..Default::default()
+        };
+        free.visit_expr(e);
+        free
+    }
+    fn is_bound(&self, name: &str) -> bool {
+        self.bound.iter().any(|b| b == name)
+    }
+}
+
+impl Visitor<()> for FreeVars {
+    fn visit_grouping_expr(&mut self, e: &GroupingExpr) {
+        self.visit_expr(&e.0)
+    }
+    fn visit_binary_expr(&mut self, e: &BinaryExpr) {
+        self.visit_expr(&e.lhs);
+        self.visit_expr(&e.rhs);
+    }
+    fn visit_unary_expr(&mut self, e: &UnaryExpr) {
+        self.visit_expr(&e.rhs)
+    }
+    fn visit_literal(&mut self, _e: &Literal) {}
+    fn visit_var(&mut self, e: &Var) {
+        if !self.is_bound(&e.name) {
+            self.vars.insert(e.name.clone());
+        }
+    }
+    fn visit_func_call(&mut self, e: &FnCall) {
+        if !self.is_bound(&e.name) {
+            self.funcs.insert(e.name.clone());
+        }
+        for arg in &e.arguments {
+            self.visit_expr(arg)
+        }
+    }
+    fn visit_lambda(&mut self, e: &Lambda) {
+        let len = self.bound.len();
+        self.bound.extend(e.arguments.iter().cloned());
+        self.visit_expr(&e.expr);
+        self.bound.truncate(len);
+    }
+    fn visit_let(&mut self, e: &LetExpr) {
+        let len = self.bound.len();
+        for (name, value) in &e.bindings {
+            self.visit_expr(value);
+            self.bound.push(name.clone());
+        }
+        self.visit_expr(&e.body);
+        self.bound.truncate(len);
+    }
+    fn visit_stmt(&mut self, s: &Stmt) {
+        match s {
+            Stmt::VarAssign(e) => self.visit_expr(&e.value),
+            Stmt::FnAssign(e) => {
+                let len = self.bound.len();
+                self.bound.extend(e.arguments.iter().cloned());
+                self.visit_expr(&e.expr);
+                self.bound.truncate(len);
+            }
+            Stmt::Expr(e) => self.visit_expr(e),
+        }
+    }
+}
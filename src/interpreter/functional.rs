@@ -0,0 +1,102 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    ast::*,
+    env::*,
+    error::Error,
+    native::{Arity, Ctx, NativeFn},
+    value::*,
+};
+
+/// Splits the leading function argument off the rest, which are flattened.
+fn fn_and_items(args: &[Value]) -> Result<(Func, Vec<Value>), Error> {
+    let (f, items) = args.split_first().ok_or("Expected a function")?;
+    Ok((f.clone().into_func()?, flatten(items.to_vec())))
+}
+
+fn map(ctx: &mut Ctx, args: &[Value]) -> Result<Value, Error> {
+    let (f, items) = fn_and_items(args)?;
+    items
+        .into_iter()
+        .map(|v| ctx.call(&f, vec![v]))
+        .collect::<Result<_, _>>()
+        .map(Value::List)
+}
+
+fn filter(ctx: &mut Ctx, args: &[Value]) -> Result<Value, Error> {
+    let (f, items) = fn_and_items(args)?;
+    let mut kept = Vec::new();
+    for v in items {
+        if !ctx.call(&f, vec![v.clone()])?.into_number()?.is_zero() {
+            kept.push(v)
+        }
+    }
+    Ok(Value::List(kept))
+}
+
+fn reduce(ctx: &mut Ctx, args: &[Value]) -> Result<Value, Error> {
+    let (f, items) = fn_and_items(args)?;
+    let mut items = items.into_iter();
+    let first = items
+        .next()
+        .ok_or("Function 'reduce' needs at least one value to reduce")?;
+    items.try_fold(first, |acc, v| ctx.call(&f, vec![acc, v]))
+}
+
+fn apply(ctx: &mut Ctx, args: &[Value]) -> Result<Value, Error> {
+    let (f, args) = fn_and_items(args)?;
+    ctx.call(&f, args)
+}
+
+/// `compose(f, g, h)` is `x -> f0(f1(f2(x)))`, with `f`, `g` and `h` captured as `f0`, `f1`
+/// and `f2`. Nothing else is referred to in the body, so the names can't clash.
+fn compose(_: &mut Ctx, args: &[Value]) -> Result<Value, Error> {
+    let mut captures = HashMap::new();
+    let mut expr = Expr::Var(Var {
+        name: "x".to_string(),
+        offset: 0,
+    });
+    for (i, f) in args.iter().enumerate().rev() {
+        let name = format!("f{i}");
+        captures.insert(name.clone(), Value::Fn(f.clone().into_func()?));
+        expr = Expr::FnCall(FnCall {
+            name,
+            arguments: vec![expr],
+            offset: 0,
+        });
+    }
+    Ok(Value::Fn(Func::UserFn(UserFn {
+        expr: Rc::new(expr),
+        arguments: vec!["x".to_string()],
+        captures,
+    })))
+}
+
+pub(super) fn insert_funcs(env: &mut Env) {
+    macro_rules! insert_funcs {
+        ($( $func:ident($params:literal): $arity:expr, $help:literal, [$($example:literal),*] );+ $(;)?) => {
+            $(
+                env.register(
+                    NativeFn::new(stringify!($func), $arity, $help, $func)
+                        .with_params($params)
+                        .with_doc(Doc {
+                            examples: &[$($example),*],
+                            domain: None,
+                        }),
+                );
+            )*
+        };
+    }
+    insert_funcs!(
+        map("f, x, ..."): Arity::AtLeast(1), "Applies a function to each value, giving a list",
+            ["map(x -> x^2, 1, 2, 3)"];
+        filter("f, x, ..."): Arity::AtLeast(1), "Keeps the values for which a function is not zero",
+            ["filter(x -> x - 2, 1, 2, 3)"];
+        reduce("f, x, y, ..."): Arity::AtLeast(2), "Combines values pairwise from the left with a function",
+            ["reduce((a, b) -> a * b, 1, 2, 3, 4)"];
+        apply("f, x, ..."): Arity::AtLeast(1), "Calls a function with the remaining args",
+            ["apply(max, 1, 5, 3)"];
+        compose("f, ..."): Arity::AtLeast(1), "Chains functions right to left: compose(f, g)(x) = f(g(x))",
+            ["map(compose(sqrt, x -> x + 7), 2, 9)"];
+    );
+}
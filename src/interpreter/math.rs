@@ -1,11 +1,11 @@
-use rug::Float;
+use rug::{float::Constant, Float};
 
-use crate::PREC_BITS;
+use super::PREC_BITS;
 
 use super::env::*;
 
 fn sum(args: &[Float]) -> Float {
-    args.into_iter()
+    args.iter()
         .fold(Float::new(PREC_BITS), |acc, f| acc + f)
 }
 fn sqrt(args: &[Float]) -> Float {
@@ -24,13 +24,39 @@ fn sin(args: &[Float]) -> Float {
     args[0].clone().sin()
 }
 
+pub (super) fn insert_consts(env: &mut Env) {
+    env.members.insert("pi".into(), EnvMember::Var(Float::with_val(PREC_BITS, Constant::Pi)));
+    env.members.insert("π".into(), EnvMember::Var(Float::with_val(PREC_BITS, Constant::Pi)));
+    env.members.insert("e".into(), EnvMember::Var(Float::with_val(PREC_BITS, 1).exp()));
+    let pi = "Ratio of a circle's circumference to its diameter";
+    env.set_doc("pi", pi);
+    env.set_doc("π", pi);
+    env.set_doc("e", "Euler's number, the base of the natural logarithm");
+}
+
 pub (super) fn insert_funcs(env: &mut Env) {
     macro_rules! insert_funcs {
-        ($( $func:ident ),+) => {
+        ($( $func:ident($params:literal), $help:literal, [$($example:literal),*] $(, $domain:literal)? );+ $(;)?) => {
             $(
-                env.members.insert(stringify!($func).into(), EnvMember::Fn(Func::BuiltinFn($func)));
+                env.members.insert(stringify!($func).into(), EnvMember::Fn(Func::BuiltinFn(BuiltinFn {
+                    name: stringify!($func),
+                    params: $params,
+                    help: $help,
+                    doc: Doc {
+                        examples: &[$($example),*],
+                        domain: None $(.or(Some($domain)))?,
+                    },
+                    func: $func,
+                })));
             )*
         };
     }
-    insert_funcs!(sum, sqrt, avg, min, max, sin);
+    insert_funcs!(
+        sum("x, ..."), "Adds up its arguments", ["sum(1, 2, 3)", "sum(map(x -> x^2, 1, 2, 3))"];
+        sqrt("x"), "Square root", ["sqrt(2)", "√16"], "Negative numbers give NaN, there are no complex results";
+        avg("x, ..."), "Arithmetic mean of its arguments", ["avg(1, 2, 3, 4)"];
+        min("x, ..."), "Smallest of its arguments", ["min(3, -1, 2)"];
+        max("x, ..."), "Largest of its arguments", ["max(3, -1, 2)"];
+        sin("x"), "Sine of an angle in radians", ["sin(pi / 6)", "sin(90 * pi / 180)"];
+    );
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::fmt::Debug;
+
+use super::{
+    env::{Doc, Func},
+    error::Error,
+    value::Value,
+    Interpreter,
+};
+
+/// How many arguments a native function accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    fn check(self, name: &str, n: usize) -> Result<(), String> {
+        match self {
+            Arity::Exact(a) if n != a => Err(format!("Function '{name}' takes {a} args")),
+            Arity::AtLeast(a) if n < a => Err(format!("Function '{name}' takes at least {a} args")),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub type NativeFnImpl = dyn Fn(&mut Ctx, &[Value]) -> Result<Value, Error>;
+
+/// A function implemented in Rust that works on whole values, so it can take functions and
+/// lists as arguments and call back into the calculator through [`Ctx`].
+pub struct NativeFn {
+    pub name: String,
+    pub arity: Arity,
+    /// Parameter names as shown in the signature, e.g. `f, x, ...`.
+    pub params: String,
+    pub help: String,
+    pub doc: Doc,
+    func: Box<NativeFnImpl>,
+}
+
+impl NativeFn {
+    pub fn new(
+        name: &str,
+        arity: Arity,
+        help: &str,
+        func: impl Fn(&mut Ctx, &[Value]) -> Result<Value, Error> + 'static,
+    ) -> Self {
+        let (n, variadic) = match arity {
+            Arity::Exact(n) => (n, false),
+            Arity::AtLeast(n) => (n, true),
+        };
+        let mut params: Vec<_> = (1..=n).map(|i| format!("x{i}")).collect();
+        if variadic {
+            params.push("...".to_string());
+        }
+        Self {
+            name: name.to_string(),
+            arity,
+            params: params.join(", "),
+            help: help.to_string(),
+            doc: Doc::default(),
+            func: Box::new(func),
+        }
+    }
+
+    /// Names the parameters for the signature instead of numbering them.
+    pub fn with_params(mut self, params: &str) -> Self {
+        self.params = params.to_string();
+        self
+    }
+
+    pub fn with_doc(mut self, doc: Doc) -> Self {
+        self.doc = doc;
+        self
+    }
+    pub(super) fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        args: &[Value],
+    ) -> Result<Value, String> {
+        self.arity.check(&self.name, args.len())?;
+        (self.func)(&mut Ctx { interpreter }, args).map_err(|e| e.message)
+    }
+}
+
+impl Debug for NativeFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFn")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a native function can do with the calculator while it runs.
+pub struct Ctx<'a> {
+    interpreter: &'a mut Interpreter,
+}
+
+impl Ctx<'_> {
+    /// Calls a function value, e.g. one passed in as an argument.
+    pub fn call(&mut self, f: &Func, args: Vec<Value>) -> Result<Value, Error> {
+        self.interpreter
+            .call("<lambda>", f, args)
+            .map_err(Error::from)
+    }
+
+    /// Looks up a variable or function the same way an expression would.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.interpreter.lookup(name)
+    }
+}
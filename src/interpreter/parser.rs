@@ -1,10 +1,10 @@
-use super::ast::*;
+use super::{ast::*, error::Error};
 use crate::scanner::{Token, TokenKind};
-use std::mem::discriminant;
+use std::{mem::discriminant, rc::Rc};
 
 pub struct Parser<'a> {
     current: usize,
-    tokens: &'a [Token],
+    tokens: Vec<Token>,
     source: &'a str,
 }
 
@@ -13,7 +13,11 @@ impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token], source: &'a str) -> Self {
         Self {
             current: 0,
-            tokens,
+            tokens: tokens
+                .iter()
+                .filter(|t| t.kind != TokenKind::Comment)
+                .cloned()
+                .collect(),
             source,
         }
     }
@@ -49,19 +53,92 @@ impl<'a> Parser<'a> {
         &self.tokens[self.current - 1]
     }
 
+    fn peek_offset(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.current + offset)
+    }
+
     fn expression(&mut self) -> Result<Expr, String> {
+        if self.match_tokens(&[TokenKind::Let]) {
+            return self.let_expr();
+        }
+        if let Some(arguments) = self.lambda_params() {
+            let expr = self.expression()?;
+            return Ok(Expr::Lambda(Lambda {
+                arguments,
+                expr: Rc::new(expr),
+            }));
+        }
         self.term()
     }
+    /// Consumes the parameter list of a lambda (`x ->` or `(a, b) ->`) if one starts at the
+    /// current token, leaving the parser untouched otherwise.
+    fn lambda_params(&mut self) -> Option<Vec<String>> {
+        let kind_at = |offset: usize| self.peek_offset(offset).map(|t| &t.kind);
+        let name_at =
+            |offset: usize| self.source[self.tokens[self.current + offset].span()].to_string();
+        if kind_at(0) == Some(&TokenKind::Indentifier) && kind_at(1) == Some(&TokenKind::Arrow) {
+            let name = name_at(0);
+            self.current += 2;
+            return Some(vec![name]);
+        }
+        if kind_at(0) != Some(&TokenKind::LParen) {
+            return None;
+        }
+        let mut params = Vec::new();
+        let mut offset = 1;
+        if kind_at(offset) != Some(&TokenKind::RParen) {
+            loop {
+                if kind_at(offset) != Some(&TokenKind::Indentifier) {
+                    return None;
+                }
+                params.push(name_at(offset));
+                offset += 1;
+                if kind_at(offset) != Some(&TokenKind::Comma) {
+                    break;
+                }
+                offset += 1;
+            }
+        }
+        if kind_at(offset) != Some(&TokenKind::RParen)
+            || kind_at(offset + 1) != Some(&TokenKind::Arrow)
+        {
+            return None;
+        }
+        self.current += offset + 2;
+        Some(params)
+    }
+    fn let_expr(&mut self) -> Result<Expr, String> {
+        let mut bindings = Vec::new();
+        loop {
+            let name = self
+                .consume(
+                    &TokenKind::Indentifier,
+                    "Expect variable name after 'let'.".into(),
+                )?
+                .span();
+            let name = self.source[name].to_string();
+            self.consume(&TokenKind::Equal, "Expect '=' after variable name.".into())?;
+            bindings.push((name, self.expression()?));
+            if !self.match_tokens(&[TokenKind::Comma]) {
+                break;
+            }
+        }
+        self.consume(&TokenKind::In, "Expect 'in' after let bindings.".into())?;
+        let body = self.expression()?;
+        Ok(Expr::Let(Box::new(LetExpr { bindings, body })))
+    }
     fn stmt(&mut self) -> Result<Stmt, String> {
         let mut expr = self.expression()?;
 
-        if self.match_tokens(&[TokenKind::Equal]) {
+        if self.match_tokens(&[TokenKind::Equal, TokenKind::ColonEqual]) {
             let equals = self.previous().clone();
+            let capture = equals.kind == TokenKind::ColonEqual;
             let value = self.expression()?;
             return match expr {
                 Expr::Var(v) => Ok(Stmt::VarAssign(VarAssign {
                     name: v.name,
                     value,
+                    capture,
                 })),
                 Expr::FnCall(f) => {
                     let args = f
@@ -76,6 +153,7 @@ impl<'a> Parser<'a> {
                         name: f.name,
                         arguments: args,
                         expr: value,
+                        capture,
                     }))
                 }
                 _ => Err("Expected function or variable assignment".to_string()),
@@ -111,7 +189,7 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     fn unary(&mut self) -> Result<Expr, String> {
-        if self.match_tokens(&[TokenKind::Minus, TokenKind::Plus]) {
+        if self.match_tokens(&[TokenKind::Minus, TokenKind::Plus, TokenKind::Sqrt]) {
             let operator = self.previous().clone();
             let rhs = self.unary()?;
             return Ok(Expr::Unary(Box::new(UnaryExpr { operator, rhs })));
@@ -120,6 +198,18 @@ impl<'a> Parser<'a> {
     }
     fn exp(&mut self) -> Result<Expr, String> {
         let mut expr = self.primary()?;
+        while self.match_tokens(&[TokenKind::Superscript]) {
+            let operator = self.previous().clone();
+            let rhs = Expr::Literal(operator.literal.clone().unwrap());
+            expr = Expr::Binary(Box::new(BinaryExpr {
+                lhs: expr,
+                operator: Token {
+                    kind: TokenKind::Exp,
+                    ..operator
+                },
+                rhs,
+            }))
+        }
         while self.match_tokens(&[TokenKind::Exp]) {
             let operator = self.previous().clone();
             let rhs = self.unary()?;
@@ -141,19 +231,26 @@ impl<'a> Parser<'a> {
             return Ok(Expr::Grouping(Box::new(GroupingExpr(expr))));
         }
         if self.match_tokens(&[TokenKind::Indentifier]) {
-            let name = self.source[self.previous().clone().span()].to_string();
+            let span = self.previous().clone().span();
+            let (name, offset) = (self.source[span.clone()].to_string(), span.start);
             if self.match_tokens(&[TokenKind::LParen]) {
                 let mut arguments = Vec::new();
-                loop {
-                    arguments.push(self.expression()?);
-                    if !self.match_tokens(&[TokenKind::Comma]) {
-                        break;
+                if !self.check(&TokenKind::RParen) {
+                    loop {
+                        arguments.push(self.expression()?);
+                        if !self.match_tokens(&[TokenKind::Comma]) {
+                            break;
+                        }
                     }
                 }
                 self.consume(&TokenKind::RParen, "Expect ')' after function call.".into())?;
-                return Ok(Expr::FnCall(FnCall { name, arguments }));
+                return Ok(Expr::FnCall(FnCall {
+                    name,
+                    arguments,
+                    offset,
+                }));
             } else {
-                return Ok(Expr::Var(Var { name }));
+                return Ok(Expr::Var(Var { name, offset }));
             }
         }
         Err("Expected expression".to_string())?
@@ -164,18 +261,43 @@ impl<'a> Parser<'a> {
         }
         Err(error)
     }
-    pub fn parse(&mut self) -> Result<Stmt, String> {
-        let res = self.stmt()?;
+    fn program(&mut self) -> Result<Program, String> {
+        let mut offsets = vec![self.peek().start];
+        let mut stmts = vec![self.stmt()?];
+        while self.match_tokens(&[TokenKind::Semicolon, TokenKind::Newline]) && !self.is_at_end() {
+            offsets.push(self.peek().start);
+            stmts.push(self.stmt()?);
+        }
         if !self.is_at_end() {
             Err("Expected EOF".to_string())?
         }
-        Ok(res)
+        Ok(Program { stmts, offsets })
+    }
+    /// Parses a lone expression, such as the body of a function.
+    pub fn parse_expr(&mut self) -> Result<Expr, Error> {
+        let res = self.expression().and_then(|e| {
+            if !self.is_at_end() {
+                Err("Expected EOF".to_string())?
+            }
+            Ok(e)
+        });
+        res.map_err(|message| Error {
+            message,
+            offset: self.peek().start,
+        })
+    }
+    pub fn parse(&mut self) -> Result<Program, Error> {
+        self.program().map_err(|message| Error {
+            message,
+            offset: self.peek().start,
+        })
     }
     pub fn error(&self, msg: String, token: &Token) -> String {
         format!("{}\n{}^ {msg}", self.source, " ".repeat(token.span().start))
     }
 }
 
+#[allow(dead_code)]
 pub struct AstPrinter<'a> {
     pub source: &'a str,
 }
@@ -215,24 +337,63 @@ impl Visitor<String> for AstPrinter<'_> {
         s.push(')');
         s
     }
+    fn visit_lambda(&mut self, e: &Lambda) -> String {
+        format!(
+            "(-> ({}) {})",
+            e.arguments.join(" "),
+            self.visit_expr(&e.expr)
+        )
+    }
+    fn visit_let(&mut self, e: &LetExpr) -> String {
+        let mut s = "(let".to_string();
+        for (name, value) in &e.bindings {
+            s.push_str(&format!(" ({name} {})", self.visit_expr(value)))
+        }
+        format!("{s} {})", self.visit_expr(&e.body))
+    }
     fn visit_stmt(&mut self, s: &Stmt) -> String {
         match s {
-            Stmt::VarAssign(e) => format!("{} = {}", e.name, self.visit_expr(&e.value)),
+            Stmt::VarAssign(e) => format!(
+                "{} {} {}",
+                e.name,
+                if e.capture { ":=" } else { "=" },
+                self.visit_expr(&e.value)
+            ),
             Stmt::FnAssign(e) => format!(
-                "{} = {}",
+                "{} {} {}",
                 self.visit_func_call(&FnCall {
                     name: e.name.clone(),
                     arguments: e
                         .arguments
                         .iter()
                         .cloned()
-                        .map(|t| Expr::Var(Var { name: t }))
-                        .collect()
+                        .map(|t| Expr::Var(Var { name: t, offset: 0 }))
+                        .collect(),
+                    offset: 0,
                 }),
+                if e.capture { ":=" } else { "=" },
                 self.visit_expr(&e.expr)
-            )
-        ,
+            ),
             Stmt::Expr(e) => self.visit_expr(e),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(src: &str) -> Result<Program, Error> {
+        let tokens = Scanner::new(src).scan_tokens().unwrap();
+        Parser::new(&tokens, src).parse()
+    }
+
+    #[test]
+    fn call_arguments() {
+        assert!(parse("f()").is_ok());
+        assert!(parse("f(1, 2)").is_ok());
+        assert!(parse("f(1, )").is_err());
+        assert!(parse("map(x -> x, )").is_err());
+    }
+}
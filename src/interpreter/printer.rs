@@ -0,0 +1,165 @@
+use super::{ast::*, env::*, value::Value};
+use crate::{
+    scanner::TokenKind,
+    util::{disp_value, exact_num, DISPLAY_DIGITS},
+};
+
+impl EnvMember {
+    /// The statement that defines this as `name`, e.g. `f(x) = x^2`, or `None` for builtins.
+    pub fn definition(&self, name: &str) -> Option<String> {
+        InfixPrinter::member(name, self)
+    }
+}
+
+impl Func {
+    /// The source of the function, e.g. `(x -> x^2)`, or a builtin's name.
+    pub fn source(&self) -> String {
+        InfixPrinter::func(self)
+    }
+}
+
+/// Prints expressions back as source that parses to the same tree, so that definitions can be
+/// saved and evaluated again later.
+pub struct InfixPrinter;
+
+impl InfixPrinter {
+    /// The source of a statement that recreates `member` as `name`, or `None` for builtins and
+    /// anything else that can't be written down.
+    pub fn member(name: &str, member: &EnvMember) -> Option<String> {
+        match member {
+            EnvMember::Var(n) => Some(format!("{name} = {}", exact_num(n))),
+            EnvMember::Fn(Func::UserFn(f)) => Some(format!(
+                "{name}({}) = {}",
+                f.arguments.join(", "),
+                InfixPrinter.fn_body(f)?
+            )),
+            EnvMember::Fn(_) => None,
+        }
+    }
+
+    /// The source of a function value: a lambda such as `(x -> x^2)`, or a builtin's name.
+    /// Functions that can't be written down are shown like results are.
+    pub fn func(f: &Func) -> String {
+        let f = Value::Fn(f.clone());
+        InfixPrinter
+            .value(&f)
+            .unwrap_or_else(|| disp_value(&f, DISPLAY_DIGITS))
+    }
+
+    /// The body of `f`, with its captures bound by a `let` around it. `None` if a capture
+    /// can't be written down, since leaving it out would make the body mean something else.
+    fn fn_body(&mut self, f: &UserFn) -> Option<String> {
+        let mut captures = f
+            .captures
+            .iter()
+            .map(|(name, v)| Some((name, self.value(v)?)))
+            .filter(|c| c.as_ref().is_none_or(|(name, v)| name != &v))
+            .map(|c| c.map(|(name, v)| format!("{name} = {v}")))
+            .collect::<Option<Vec<_>>>()?;
+        captures.sort();
+        let body = self.visit_expr(&f.expr);
+        Some(if captures.is_empty() {
+            body
+        } else {
+            format!("let {} in {body}", captures.join(", "))
+        })
+    }
+
+    /// Builtins are referred to by name. Lists can't be written down.
+    fn value(&mut self, v: &Value) -> Option<String> {
+        match v {
+            Value::Number(n) => Some(exact_num(n)),
+            Value::Fn(Func::UserFn(f)) => Some(format!(
+                "({} -> {})",
+                Self::params(&f.arguments),
+                self.fn_body(f)?
+            )),
+            Value::Fn(Func::BuiltinFn(f)) => Some(f.name.to_string()),
+            Value::Fn(Func::NativeFn(f)) => Some(f.name.clone()),
+            Value::List(_) => None,
+        }
+    }
+
+    fn params(arguments: &[String]) -> String {
+        match arguments {
+            [arg] => arg.clone(),
+            args => format!("({})", args.join(", ")),
+        }
+    }
+}
+
+impl Visitor<String> for InfixPrinter {
+    fn visit_grouping_expr(&mut self, e: &GroupingExpr) -> String {
+        format!("({})", self.visit_expr(&e.0))
+    }
+    fn visit_binary_expr(&mut self, e: &BinaryExpr) -> String {
+        let lhs = self.visit_expr(&e.lhs);
+        let rhs = self.visit_expr(&e.rhs);
+        match e.operator.kind {
+            TokenKind::Plus => format!("{lhs} + {rhs}"),
+            TokenKind::Minus => format!("{lhs} - {rhs}"),
+            TokenKind::Star => format!("{lhs} * {rhs}"),
+            TokenKind::Slash => format!("{lhs} / {rhs}"),
+            TokenKind::Exp => format!("{lhs}^{rhs}"),
+            ref t => panic!("Unexpected Token {t:?}"),
+        }
+    }
+    fn visit_unary_expr(&mut self, e: &UnaryExpr) -> String {
+        let rhs = self.visit_expr(&e.rhs);
+        match e.operator.kind {
+            TokenKind::Minus => format!("-{rhs}"),
+            TokenKind::Plus => format!("+{rhs}"),
+            TokenKind::Sqrt => format!("√{rhs}"),
+            ref t => panic!("Unexpected Token {t:?}"),
+        }
+    }
+    fn visit_literal(&mut self, e: &Literal) -> String {
+        match e {
+            Literal::Number(n) => exact_num(n),
+        }
+    }
+    fn visit_var(&mut self, e: &Var) -> String {
+        e.name.clone()
+    }
+    fn visit_func_call(&mut self, e: &FnCall) -> String {
+        let args: Vec<_> = e.arguments.iter().map(|a| self.visit_expr(a)).collect();
+        format!("{}({})", e.name, args.join(", "))
+    }
+    fn visit_lambda(&mut self, e: &Lambda) -> String {
+        format!(
+            "{} -> {}",
+            Self::params(&e.arguments),
+            self.visit_expr(&e.expr)
+        )
+    }
+    fn visit_let(&mut self, e: &LetExpr) -> String {
+        let bindings: Vec<_> = e
+            .bindings
+            .iter()
+            .map(|(name, value)| format!("{name} = {}", self.visit_expr(value)))
+            .collect();
+        format!(
+            "let {} in {}",
+            bindings.join(", "),
+            self.visit_expr(&e.body)
+        )
+    }
+    fn visit_stmt(&mut self, s: &Stmt) -> String {
+        match s {
+            Stmt::VarAssign(e) => format!(
+                "{} {} {}",
+                e.name,
+                if e.capture { ":=" } else { "=" },
+                self.visit_expr(&e.value)
+            ),
+            Stmt::FnAssign(e) => format!(
+                "{}({}) {} {}",
+                e.name,
+                e.arguments.join(", "),
+                if e.capture { ":=" } else { "=" },
+                self.visit_expr(&e.expr)
+            ),
+            Stmt::Expr(e) => self.visit_expr(e),
+        }
+    }
+}
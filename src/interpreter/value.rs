@@ -0,0 +1,48 @@
+use rug::Float;
+
+use super::env::Func;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(Float),
+    Fn(Func),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Fn(_) => "function",
+            Value::List(_) => "list",
+        }
+    }
+    pub fn into_number(self) -> Result<Float, String> {
+        match self {
+            Value::Number(n) => Ok(n),
+            v => Err(format!("Expected a number, found a {}", v.type_name())),
+        }
+    }
+    pub fn into_func(self) -> Result<Func, String> {
+        match self {
+            Value::Fn(f) => Ok(f),
+            v => Err(format!("Expected a function, found a {}", v.type_name())),
+        }
+    }
+}
+
+impl From<Float> for Value {
+    fn from(n: Float) -> Self {
+        Value::Number(n)
+    }
+}
+
+/// Expands list arguments in place, so that `sum(map(f, 1, 2), 3)` sees `1`, `2` and `3` alike.
+pub fn flatten(args: Vec<Value>) -> Vec<Value> {
+    args.into_iter()
+        .flat_map(|v| match v {
+            Value::List(l) => l,
+            v => vec![v],
+        })
+        .collect()
+}
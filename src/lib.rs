@@ -0,0 +1,32 @@
+//! An arbitrary precision calculator.
+//!
+//! [`Calculator`] is the entry point for embedding: it keeps the variables and functions of a
+//! session and evaluates lines of input against them.
+//!
+//! ```
+//! use ferricalc::{disp_value, Calculator};
+//!
+//! let mut calc = Calculator::new();
+//! calc.eval("r = 2").unwrap();
+//! calc.define("area", &["r"], "pi * r^2").unwrap();
+//! let res = calc.eval("area(r) / pi").unwrap();
+//! assert_eq!(disp_value(&res, 16), "4");
+//! ```
+
+mod calculator;
+pub(crate) mod interpreter;
+pub(crate) mod scanner;
+pub(crate) mod util;
+
+pub use calculator::Calculator;
+pub use interpreter::{
+    env::{Doc, Env, EnvMember, Func},
+    error::Error,
+    native,
+    value::Value,
+    PREC_BITS,
+};
+pub use scanner::{is_ident_char, is_ident_start, Scanner, Token, TokenKind};
+pub use util::{
+    disp_num, disp_value, format_num, format_value, Format, Notation, DISPLAY_DIGITS, MAX_DIGITS,
+};
@@ -1,99 +1,423 @@
-use std::{error::Error, io, mem, ops::ControlFlow};
-mod scanner;
+use std::{
+    env,
+    error::Error,
+    io::{self, IsTerminal, Stdout},
+    mem,
+    ops::{ControlFlow, Range},
+    process::ExitCode,
+};
 
-use scanner::{Scanner, Token, TokenKind};
-mod interpreter;
-use interpreter::{ast::Visitor, env::Env, parser::Parser, *};
+use complete::{Completion, Recency};
+use config::{Config, EditMode, Matching};
+use editor::{
+    get_ident_around, line_down, line_end, line_start, line_up, match_parens, next_grapheme,
+    next_word, prev_grapheme, prev_word, unclosed_parens, KillRing, LastAction, State, Undo,
+};
+use ferricalc::{format_value, Calculator, Env, Format, Scanner, Token, TokenKind};
+use history::History;
+use inspector::Inspector;
+use plot::Plot;
 use ratatui::{prelude::*, symbols::border, widgets::*};
-
-mod util;
-
+use session::AutoSave;
+use unicode_width::UnicodeWidthStr;
+use vi::{Command, Mode, Vi};
+
+mod commands;
+mod complete;
+mod config;
+mod editor;
+mod help;
+mod history;
+mod inspector;
+mod plot;
+mod script;
+mod session;
+mod table;
+mod vi;
+
+use commands::After;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
 };
-use util::*;
 
-struct Completion {
-    index: usize,
-    completions: Vec<String>,
+const COMPLETION_WIDTH: u16 = 32;
+
+/// Reverse incremental search through the history.
+#[derive(Default)]
+struct Search {
+    query: String,
+    found: Option<usize>,
 }
 
+#[derive(Default)]
 struct App {
     tokens: Vec<Token>,
     input: String,
     /// Position of cursor in the editor area.
     cursor_position: usize,
     message: String,
-    history: Vec<String>,
+    history: History,
     history_index: usize,
     completion: Option<Completion>,
-}
-
-impl Default for App {
-    fn default() -> App {
-        App {
-            input: String::new(),
-            cursor_position: 0,
-            tokens: Vec::new(),
-            message: String::new(),
-            history: Vec::new(),
-            history_index: 0,
-            completion: None,
-        }
-    }
+    matching: Matching,
+    recency: Recency,
+    search: Option<Search>,
+    autosave: AutoSave,
+    kill_ring: KillRing,
+    undo: Undo,
+    last_action: LastAction,
+    /// Set when editing in vi mode.
+    vi: Option<Vi>,
+    auto_pair: bool,
+    /// How results are written out.
+    format: Format,
+    /// Set while the screen is taken over by the inspector sidebar.
+    inspector: Option<Inspector>,
+    plot_requested: plot::Requested,
+    table_requested: table::Requested,
+    /// The last table printed, for `:csv`.
+    table: Option<table::Values>,
+    /// The plot being shown, which gets all keys until it's closed.
+    plot: Option<Plot>,
 }
 
 impl App {
     fn move_cursor_left(&mut self) {
-        let cursor_moved_left = self.cursor_position.saturating_sub(1);
-        self.cursor_position = self.clamp_cursor(cursor_moved_left);
+        self.cursor_position = prev_grapheme(&self.input, self.cursor_position);
     }
 
     fn move_cursor_right(&mut self) {
-        let cursor_moved_right = self.cursor_position.saturating_add(1);
-        self.cursor_position = self.clamp_cursor(cursor_moved_right);
+        self.cursor_position = next_grapheme(&self.input, self.cursor_position);
     }
     fn incr_history(&mut self) {
         if self.history_index < self.history.len() {
             self.history_index += 1;
+            self.undo.save(self.state());
             self.input = self
                 .history
                 .get(self.history_index)
-                .cloned()
-                .unwrap_or(String::new());
+                .unwrap_or_default()
+                .to_string();
             self.cursor_position = self.input.len()
         }
     }
     fn decr_history(&mut self) {
         if self.history_index > 0 {
             self.history_index -= 1;
-            self.input = self.history[self.history_index].clone();
+            self.undo.save(self.state());
+            self.input = self.history.get(self.history_index).unwrap().to_string();
             self.cursor_position = self.input.len()
         }
     }
-    fn enter_char(&mut self, new_char: char) {
-        self.input.insert(self.cursor_position, new_char);
+    /// Finds the newest match for the query, or the next older one with `older`, skipping
+    /// entries that read the same as the current match.
+    fn search_history(&mut self, older: bool) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let current = search.found.and_then(|i| self.history.get(i));
+        let mut before = match search.found {
+            Some(i) if older => i,
+            _ => self.history.len(),
+        };
+        let found = loop {
+            match self.history.search(&search.query, before) {
+                Some(i) if older && self.history.get(i) == current => before = i,
+                found => break found,
+            }
+        };
+        // keep the last match when cycling runs out
+        if found.is_some() || !older {
+            search.found = found;
+        }
+    }
 
-        self.move_cursor_right();
+    /// Handles a key while searching the history, returning false if `key` ended the search
+    /// and should be handled as usual, like readline does.
+    fn handle_search_key(&mut self, key: KeyEvent) -> bool {
+        let Some(search) = &mut self.search else {
+            return false;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let plain = !key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('r') if ctrl => self.search_history(true),
+            KeyCode::Char('g') if ctrl => self.search = None,
+            KeyCode::Char(c) if plain => {
+                search.query.push(c);
+                self.search_history(false)
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                self.search_history(false)
+            }
+            KeyCode::Esc => self.search = None,
+            _ => {
+                if let Some(input) = search.found.and_then(|i| self.history.get(i)) {
+                    self.input = input.to_string();
+                    self.cursor_position = self.input.len();
+                    self.history_index = search.found.unwrap();
+                }
+                self.search = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn state(&self) -> State {
+        (self.input.clone(), self.cursor_position)
     }
 
-    fn delete_char(&mut self) {
-        let is_not_cursor_leftmost = self.cursor_position != 0;
-        if is_not_cursor_leftmost {
-            let current_index = self.cursor_position;
-            let from_left_to_current_index = current_index - 1;
+    fn restore(&mut self, state: Option<State>) {
+        if let Some((input, cursor)) = state {
+            self.input = input;
+            self.cursor_position = cursor;
+        }
+    }
 
-            let before_char_to_delete = self.input.chars().take(from_left_to_current_index);
-            let after_char_to_delete = self.input.chars().skip(current_index);
+    /// Shows the inspector sidebar, or hides it if it's showing.
+    fn toggle_inspector(&mut self) {
+        self.inspector = match self.inspector.take() {
+            Some(_) => None,
+            None => Some(Inspector::default()),
+        };
+    }
+
+    /// Replaces `range` with `text`, leaving the cursor after it.
+    fn splice(&mut self, range: Range<usize>, text: &str) {
+        self.undo.save(self.state());
+        self.input.replace_range(range.clone(), text);
+        self.cursor_position = range.start + text.len();
+    }
+
+    /// Removes `range` into the kill ring.
+    fn kill(&mut self, range: Range<usize>, extend: bool) {
+        let before = range.end == self.cursor_position;
+        self.kill_ring
+            .kill(self.input[range.clone()].to_string(), extend, before);
+        self.splice(range, "");
+        self.last_action = LastAction::Kill;
+    }
 
-            self.input = before_char_to_delete.chain(after_char_to_delete).collect();
+    /// Clears the input after it has been evaluated, adding it to the history.
+    fn submit(&mut self, result: Option<String>) -> io::Result<()> {
+        self.recency.use_source(&self.input);
+        let saved = self.history.push(mem::take(&mut self.input), result);
+        self.history_index = self.history.len();
+        self.undo = Undo::default();
+        self.reset_cursor();
+        if let Some(vi) = &mut self.vi {
+            vi.reset();
+        }
+        saved
+    }
+
+    fn set_vi_mode(&mut self, mode: Mode) {
+        if let Some(vi) = &mut self.vi {
+            vi.mode = mode;
+        }
+    }
+
+    fn run_vi_command(&mut self, cmd: Command) {
+        let cursor = self.cursor_position;
+        match cmd {
+            Command::Move(m) => self.cursor_position = vi::target(&self.input, cursor, m),
+            Command::Delete(m) => self.kill(vi::range(&self.input, cursor, m), false),
+            Command::Change(m) => {
+                self.kill(vi::range(&self.input, cursor, m), false);
+                self.set_vi_mode(Mode::Insert);
+            }
+            Command::Insert(m) => {
+                if let Some(m) = m {
+                    self.cursor_position = vi::target(&self.input, cursor, m);
+                }
+                self.set_vi_mode(Mode::Insert);
+            }
+            Command::Paste => {
+                if let Some(text) = self.kill_ring.yank().map(str::to_string) {
+                    let at = next_grapheme(&self.input, cursor);
+                    self.splice(at..at, &text);
+                    self.move_cursor_left();
+                }
+            }
+            Command::Undo => {
+                let state = self.undo.undo(self.state());
+                self.restore(state)
+            }
+            Command::HistoryPrev => self.decr_history(),
+            Command::HistoryNext => self.incr_history(),
+            Command::Repeat => {
+                let Some((cmd, text)) = self.vi.as_ref().and_then(|vi| vi.last_change.clone())
+                else {
+                    return;
+                };
+                self.run_vi_command(cmd);
+                if self.vi.as_ref().is_some_and(|vi| vi.mode == Mode::Insert) {
+                    let at = self.cursor_position;
+                    self.splice(at..at, &text);
+                    self.set_vi_mode(Mode::Normal);
+                    self.move_cursor_left();
+                }
+            }
+        }
+        // the cursor sits on a character in normal mode
+        if self.vi.as_ref().is_some_and(|vi| vi.mode == Mode::Normal)
+            && self.cursor_position == self.input.len()
+        {
             self.move_cursor_left();
         }
     }
 
-    fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.input.len())
+    /// Handles the keys that mean something different in vi mode, returning true if `key` was
+    /// one of them.
+    fn handle_vi_key(&mut self, key: KeyEvent) -> bool {
+        let Some(vi) = &mut self.vi else {
+            return false;
+        };
+        let plain = !key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match (vi.mode, key.code) {
+            (Mode::Insert, KeyCode::Esc) => {
+                vi.escape();
+                self.completion = None;
+                self.move_cursor_left();
+            }
+            (Mode::Normal, KeyCode::Esc) => vi.escape(),
+            (Mode::Normal, KeyCode::Char(c)) if plain => {
+                if let Some(cmd) = vi.normal_key(c) {
+                    self.run_vi_command(cmd);
+                }
+            }
+            (Mode::Insert, KeyCode::Char(c)) if plain => {
+                vi.record(Some(c));
+                return false;
+            }
+            (Mode::Insert, KeyCode::Backspace) => {
+                vi.record(None);
+                return false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Applies an editing key, returning false for keys that aren't about editing.
+    fn handle_edit_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let last = mem::take(&mut self.last_action);
+        let cursor = self.cursor_position;
+        let len = self.input.len();
+        match key.code {
+            KeyCode::Home => self.cursor_position = line_start(&self.input, cursor),
+            KeyCode::Char('a') if ctrl => self.cursor_position = line_start(&self.input, cursor),
+            KeyCode::End => self.cursor_position = line_end(&self.input, cursor),
+            KeyCode::Char('e') if ctrl => self.cursor_position = line_end(&self.input, cursor),
+            KeyCode::Left if ctrl || alt => self.cursor_position = prev_word(&self.input, cursor),
+            KeyCode::Char('b') if alt => self.cursor_position = prev_word(&self.input, cursor),
+            KeyCode::Right if ctrl || alt => self.cursor_position = next_word(&self.input, cursor),
+            KeyCode::Char('f') if alt => self.cursor_position = next_word(&self.input, cursor),
+            KeyCode::Left => self.move_cursor_left(),
+            KeyCode::Char('b') if ctrl => self.move_cursor_left(),
+            KeyCode::Right => self.move_cursor_right(),
+            KeyCode::Char('f') if ctrl => self.move_cursor_right(),
+            KeyCode::Backspace if ctrl || alt => self.kill(
+                prev_word(&self.input, cursor)..cursor,
+                last == LastAction::Kill,
+            ),
+            KeyCode::Char('w') if ctrl => self.kill(
+                prev_word(&self.input, cursor)..cursor,
+                last == LastAction::Kill,
+            ),
+            KeyCode::Char('d') if alt => self.kill(
+                cursor..next_word(&self.input, cursor),
+                last == LastAction::Kill,
+            ),
+            KeyCode::Char('u') if ctrl => self.kill(
+                line_start(&self.input, cursor)..cursor,
+                last == LastAction::Kill,
+            ),
+            // at the end of a line this joins the next one, like in emacs
+            KeyCode::Char('k') if ctrl => {
+                let end = match line_end(&self.input, cursor) {
+                    end if end == cursor && end < len => end + 1,
+                    end => end,
+                };
+                self.kill(cursor..end, last == LastAction::Kill)
+            }
+            KeyCode::Enter if alt || key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.splice(cursor..cursor, "\n")
+            }
+            KeyCode::Char('y') if ctrl => {
+                if let Some(text) = self.kill_ring.yank().map(str::to_string) {
+                    self.splice(cursor..cursor, &text);
+                    self.last_action = LastAction::Yank(cursor..self.cursor_position);
+                }
+            }
+            KeyCode::Char('y') if alt => {
+                if let LastAction::Yank(range) = last {
+                    let text = self.kill_ring.rotate().unwrap_or_default().to_string();
+                    self.splice(range.clone(), &text);
+                    self.last_action = LastAction::Yank(range.start..self.cursor_position);
+                }
+            }
+            KeyCode::Char('z') if ctrl => {
+                let state = self.undo.undo(self.state());
+                self.restore(state)
+            }
+            KeyCode::Char('z' | 'Z') if alt => {
+                let state = self.undo.redo(self.state());
+                self.restore(state)
+            }
+            KeyCode::Char(_) if ctrl || alt => {}
+            KeyCode::Char('(') if self.auto_pair => {
+                self.splice(cursor..cursor, "()");
+                self.cursor_position = cursor + 1;
+            }
+            // step over a `)` that is already there
+            KeyCode::Char(')')
+                if self.auto_pair
+                    && self.input[cursor..].starts_with(')')
+                    && unclosed_parens(&self.tokens) == 0 =>
+            {
+                self.cursor_position += 1
+            }
+            KeyCode::Backspace
+                if self.auto_pair
+                    && self.input[..cursor].ends_with('(')
+                    && self.input[cursor..].starts_with(')') =>
+            {
+                self.splice(cursor - 1..cursor + 1, "")
+            }
+            KeyCode::Char(c) => {
+                // typing a run of characters is undone in one go
+                if last != LastAction::Insert {
+                    self.undo.save(self.state());
+                }
+                self.input.insert(cursor, c);
+                self.cursor_position += c.len_utf8();
+                self.last_action = LastAction::Insert;
+            }
+            KeyCode::Backspace if cursor > 0 => {
+                self.splice(prev_grapheme(&self.input, cursor)..cursor, "")
+            }
+            KeyCode::Delete if cursor < len => {
+                self.splice(cursor..next_grapheme(&self.input, cursor), "")
+            }
+            KeyCode::Backspace | KeyCode::Delete => {}
+            _ => {
+                self.last_action = last;
+                return false;
+            }
+        }
+        true
     }
 
     fn reset_cursor(&mut self) {
@@ -101,31 +425,61 @@ impl App {
     }
 
     fn update_completions(&mut self, env: &Env) {
-        self.completion = get_ident_at_end(&self.input[..self.cursor_position]).and_then(|s| {
-            let completions: Vec<_> = env.search(s).map(|(name, _)| name.to_string()).collect();
-            (!completions.is_empty()).then(|| Completion {
-                index: 0,
-                completions,
-            })
-        })
+        let before_cursor = &self.input[..self.cursor_position];
+        let in_comment = Scanner::new(before_cursor).scan_tokens().is_ok_and(|t| {
+            t.iter()
+                .rev()
+                .nth(1)
+                .is_some_and(|t| t.kind == TokenKind::Comment)
+        });
+        if in_comment || self.input.starts_with(':') {
+            self.completion = None;
+            return;
+        }
+        self.completion = get_ident_around(&self.input, self.cursor_position)
+            .and_then(|r| Completion::new(&self.input[r], env, self.matching, &self.recency))
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("run") => return Ok(script::run_cli(&args[1..])),
+        Some("-e" | "--eval") => return Ok(script::eval_cli(&args)),
+        Some(_) => {
+            eprintln!("{}", script::USAGE);
+            return Ok(ExitCode::from(2));
+        }
+        // never take over a terminal we aren't reading from
+        None if !io::stdin().is_terminal() => return Ok(script::run_stdin()),
+        None => {}
+    }
+
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        Config::default()
+    });
+
     // setup terminal
     enable_raw_mode()?;
-    let stdout = io::stdout();
-
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::with_options(
-        backend,
-        TerminalOptions {
-            viewport: Viewport::Inline(8),
-        },
-    )?;
+    let mut terminal = inline_terminal(VIEWPORT_HEIGHT)?;
 
     // create app and run it
-    let app = App::default();
+    let history = History::load();
+    let mut recency = Recency::default();
+    for entry in history.iter() {
+        recency.use_source(&entry.input);
+    }
+    let app = App {
+        history_index: history.len(),
+        history,
+        vi: (config.edit_mode == EditMode::Vi).then(Vi::default),
+        auto_pair: config.auto_pair,
+        matching: config.completion,
+        recency,
+        format: config.format,
+        ..Default::default()
+    };
     let res = run_app(&mut terminal, app);
 
     // restore terminal
@@ -137,99 +491,247 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("{err:?}");
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+const VIEWPORT_HEIGHT: u16 = 8;
+/// Digits in the cells of tables, at most, to keep them narrow.
+const TABLE_DIGITS: usize = 12;
+
+fn input_lines(input: &str) -> u16 {
+    input.split('\n').count() as u16
 }
 
-fn color_tokens(buf: &mut Buffer, tokens: &[Token], x: u16, y: u16) {
+/// Column and row of byte `pos` of `s` on the screen.
+fn screen_pos(s: &str, pos: usize) -> (u16, u16) {
+    let before = &s[..pos];
+    let row = before.matches('\n').count() as u16;
+    (width(&before[line_start(s, pos)..]), row)
+}
+
+/// Terminal columns taken up by `s`.
+fn width(s: &str) -> u16 {
+    s.width() as u16
+}
+
+/// Highlights `tokens` of `source` drawn at `x`, `y`. Unmatched parentheses are flagged and,
+/// given the `cursor`, the pair it is on is marked.
+fn color_tokens(
+    buf: &mut Buffer,
+    source: &str,
+    tokens: &[Token],
+    cursor: Option<usize>,
+    x: u16,
+    y: u16,
+) {
+    let (pairs, unmatched) = match_parens(tokens);
+    let at_cursor = |pos: fn(&Token) -> usize| {
+        pairs.iter().find(|(open, close)| {
+            [open, close]
+                .iter()
+                .any(|&&i| Some(pos(&tokens[i])) == cursor)
+        })
+    };
+    // prefer the parenthesis after the cursor to the one before it
+    let current = at_cursor(|t| t.start).or_else(|| at_cursor(|t| t.end));
     for (i, t) in tokens.iter().enumerate() {
         let peek = tokens.get(i + 1).map(|Token { kind, .. }| kind);
+        let (col, row) = screen_pos(source, t.start);
+        let area = Rect::new(x + col, y + row, width(&source[t.span()]), 1);
         buf.set_style(
-            Rect::new(x + t.start as u16, y, (t.end - t.start) as u16, 1),
+            area.intersection(buf.area),
             match t.kind {
                 TokenKind::Plus
                 | TokenKind::Slash
                 | TokenKind::Minus
                 | TokenKind::Star
-                | TokenKind::Exp => Style::default().fg(Color::LightCyan),
+                | TokenKind::Exp
+                | TokenKind::Sqrt
+                | TokenKind::Superscript
+                | TokenKind::Arrow => Style::default().fg(Color::LightCyan),
                 TokenKind::Number => Style::default().fg(Color::Magenta),
                 TokenKind::Indentifier if peek == Some(&TokenKind::LParen) => {
                     Style::default().fg(Color::Blue)
                 }
                 TokenKind::Indentifier => Style::default().fg(Color::Red),
+                TokenKind::Let | TokenKind::In => Style::default().fg(Color::Yellow),
+                TokenKind::Comment => Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::DIM),
+                _ if unmatched.contains(&i) => Style::default().fg(Color::Red).bold(),
+                _ if current.is_some_and(|&(open, close)| i == open || i == close) => {
+                    Style::default().fg(Color::Yellow).bold()
+                }
                 TokenKind::LParen | TokenKind::RParen => Style::default().fg(Color::DarkGray),
                 _ => Style::default(),
             },
         );
     }
 }
-fn handle_key_event<B: Backend>(
+fn print_error<B: Backend>(terminal: &mut Terminal<B>, msg: String) -> io::Result<()> {
+    terminal.insert_before(1, |b| Paragraph::new(msg).red().render(b.area, b))
+}
+
+fn handle_key_event(
     key: KeyEvent,
-    terminal: &mut Terminal<B>,
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     app: &mut App,
-    interpreter: &mut Interpreter,
+    calc: &mut Calculator,
 ) -> io::Result<ControlFlow<()>> {
     if key.kind == KeyEventKind::Press {
+        if app.handle_search_key(key) {
+            return Ok(ControlFlow::Continue(()));
+        }
+        if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            app.search = Some(Search::default());
+            app.completion = None;
+            return Ok(ControlFlow::Continue(()));
+        }
+        if let Some(plot) = &mut app.plot {
+            if !plot.handle_key(key.code) {
+                app.plot = None;
+            }
+            return Ok(ControlFlow::Continue(()));
+        }
+        if key.code == KeyCode::F(2) {
+            app.toggle_inspector();
+            return Ok(ControlFlow::Continue(()));
+        }
+        if let Some(insp) = app.inspector.as_mut().filter(|i| i.focused) {
+            if let Some(name) = insp.handle_key(key.code) {
+                let cursor = app.cursor_position;
+                app.splice(cursor..cursor, &name);
+            }
+            return Ok(ControlFlow::Continue(()));
+        }
+        if key.code == KeyCode::Tab && app.completion.is_none() {
+            if let Some(insp) = &mut app.inspector {
+                insp.focused = true;
+                return Ok(ControlFlow::Continue(()));
+            }
+        }
+        if app.handle_vi_key(key) {
+            return Ok(ControlFlow::Continue(()));
+        }
         if let Some(comp) = &mut app.completion {
             match key.code {
-                KeyCode::Enter | KeyCode::Tab => {
-                    if let Some(r) = get_ident_range(&app.input, app.cursor_position) {
-                        app.input
-                            .replace_range(r.clone(), &comp.completions[comp.index]);
-                        app.cursor_position = r.start + comp.completions[comp.index].len()
+                // Shift-Enter and Alt-Enter still start a new line
+                KeyCode::Enter | KeyCode::Tab if key.modifiers.is_empty() => {
+                    if let Some(r) = get_ident_around(&app.input, app.cursor_position) {
+                        let cand = &comp.candidates[comp.index];
+                        let mut text = cand.name.clone();
+                        if cand.is_fn && !app.input[r.end..].starts_with('(') {
+                            text.push_str(if app.auto_pair { "()" } else { "(" });
+                        }
+                        app.input.replace_range(r.clone(), &text);
+                        app.cursor_position = r.start + cand.name.len();
+                        if cand.is_fn {
+                            app.cursor_position += 1;
+                        }
                     }
+                    // accepting a candidate with Enter shouldn't evaluate the input too
+                    app.completion = None;
+                    return Ok(ControlFlow::Continue(()));
                 }
-                KeyCode::Down => comp.index = (comp.index + 1).min(comp.completions.len() - 1),
+                KeyCode::Down => comp.index = (comp.index + 1).min(comp.candidates.len() - 1),
                 KeyCode::Up => comp.index = comp.index.saturating_sub(1),
                 _ => {}
             }
         } else {
             match key.code {
-                KeyCode::Up => app.decr_history(),
-                KeyCode::Down => app.incr_history(),
+                KeyCode::Up => match line_up(&app.input, app.cursor_position) {
+                    Some(pos) => app.cursor_position = pos,
+                    None => app.decr_history(),
+                },
+                KeyCode::Down => match line_down(&app.input, app.cursor_position) {
+                    Some(pos) => app.cursor_position = pos,
+                    None => app.incr_history(),
+                },
                 _ => {}
             }
         };
+        let before = app.input.clone();
+        if app.handle_edit_key(key) {
+            if app.input != before {
+                app.update_completions(calc.env());
+            }
+            return Ok(ControlFlow::Continue(()));
+        }
         match key.code {
-            KeyCode::Enter => {
-                let res = Parser::new(&app.tokens, &app.input)
-                    .parse()
-                    .and_then(|e| interpreter.visit_stmt_owned(e));
-                if let Ok(res) = res {
-                    interpreter.last_ans = res.clone();
-                    terminal.insert_before(3, |b| {
-                        Paragraph::new(vec![
-                            Line::raw(""),
-                            Line::raw(&app.input),
-                            Line::from(vec![
-                                Span::raw("= "),
-                                Span::styled(
-                                    disp_num(&res, DISPLAY_DIGITS).unwrap(),
-                                    Style::default().fg(Color::Red),
-                                ),
-                            ]),
-                        ])
-                        .render(b.area, b);
-                        color_tokens(b, &app.tokens, 0, 1);
+            KeyCode::Enter if app.input.starts_with([':', '?']) => {
+                let cmd = match app.input.strip_prefix('?') {
+                    Some(topic) => format!("help {topic}"),
+                    None => app.input[1..].to_string(),
+                };
+                let (msg, color, after) = match commands::run(&cmd, app, calc) {
+                    Ok((msg, after)) => (msg, Color::Reset, after),
+                    Err(msg) => (msg, Color::Red, After::Continue),
+                };
+                if after == After::Clear {
+                    clear_screen(terminal)?;
+                } else {
+                    let mut lines = vec![Line::raw(""), Line::raw(&app.input)];
+                    lines.extend(
+                        msg.lines()
+                            .map(|l| Line::styled(l, Style::default().fg(color))),
+                    );
+                    terminal.insert_before(lines.len() as u16, |b| {
+                        Paragraph::new(lines).render(b.area, b);
                     })?;
-
-                    app.history.push(mem::take(&mut app.input));
-                    app.history_index = app.history.len();
-                    app.reset_cursor()
+                }
+                if let Err(e) = app.submit(None) {
+                    print_error(terminal, format!("Failed to save history: {e}"))?;
+                }
+                if after == After::Quit {
+                    return Ok(ControlFlow::Break(()));
                 }
             }
-            KeyCode::Char(to_insert) if to_insert.is_ascii() => {
-                app.enter_char(to_insert);
-                app.update_completions(&interpreter.env);
-            }
-            KeyCode::Backspace => {
-                app.delete_char();
-                app.update_completions(&interpreter.env);
-            }
-            KeyCode::Left => {
-                app.move_cursor_left();
+            // keep going until the parentheses are closed
+            KeyCode::Enter if unclosed_parens(&app.tokens) > 0 => {
+                let cursor = app.cursor_position;
+                app.splice(cursor..cursor, "\n");
             }
-            KeyCode::Right => {
-                app.move_cursor_right();
+            KeyCode::Enter => {
+                // previews call plot() and table() too, so only take what this evaluation asks for
+                app.plot_requested.take();
+                app.table_requested.take();
+                if let Ok(res) = calc.eval(&app.input) {
+                    app.plot = app.plot_requested.take();
+                    let res = format_value(&res, &app.format);
+                    let mut lines = vec![Line::raw("")];
+                    lines.extend(app.input.split('\n').map(Line::raw));
+                    terminal.insert_before(lines.len() as u16 + 1, |b| {
+                        lines.push(Line::from(vec![
+                            Span::styled(
+                                format!("${} ", calc.results().len()),
+                                Style::default().fg(Color::DarkGray),
+                            ),
+                            Span::raw("= "),
+                            Span::styled(&res, Style::default().fg(Color::Red)),
+                        ]));
+                        Paragraph::new(lines).render(b.area, b);
+                        color_tokens(b, &app.input, &app.tokens, None, 0, 1);
+                    })?;
+                    if let Some(table) = app.table_requested.take() {
+                        let table = table.compute(calc);
+                        let lines = table.lines(&Format {
+                            digits: app.format.digits.min(TABLE_DIGITS),
+                            ..app.format
+                        });
+                        terminal.insert_before(lines.len() as u16, |b| {
+                            Paragraph::new(lines.into_iter().map(Line::from).collect::<Vec<_>>())
+                                .render(b.area, b);
+                        })?;
+                        app.table = Some(table);
+                    }
+                    if let Err(e) = app.autosave.after_eval(calc) {
+                        print_error(terminal, format!("Auto-save failed: {e}"))?;
+                    }
+
+                    if let Err(e) = app.submit(Some(res)) {
+                        print_error(terminal, format!("Failed to save history: {e}"))?;
+                    }
+                }
             }
             KeyCode::Esc => {
                 return Ok(ControlFlow::Break(()));
@@ -240,27 +742,90 @@ fn handle_key_event<B: Backend>(
     Ok(ControlFlow::Continue(()))
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
-    let mut interpreter = Interpreter::new();
+/// Recreates the inline viewport when it should be `height` lines high but isn't.
+fn fit_viewport(terminal: &mut Terminal<CrosstermBackend<Stdout>>, height: u16) -> io::Result<()> {
+    let area = terminal.get_frame().size();
+    if area.height == height.min(terminal.size()?.height) {
+        return Ok(());
+    }
+    terminal.clear()?;
+    terminal.set_cursor(0, area.y)?;
+    *terminal = inline_terminal(height)?;
+    Ok(())
+}
+
+fn inline_terminal(height: u16) -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    Terminal::with_options(
+        CrosstermBackend::new(io::stdout()),
+        TerminalOptions {
+            viewport: Viewport::Inline(height),
+        },
+    )
+}
+
+/// Clears the screen and its scrollback, leaving the viewport at the top.
+fn clear_screen(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    let height = terminal.get_frame().size().height;
+    execute!(
+        terminal.backend_mut(),
+        Clear(ClearType::Purge),
+        Clear(ClearType::All),
+        MoveTo(0, 0)
+    )?;
+    *terminal = inline_terminal(height)?;
+    Ok(())
+}
+
+fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, mut app: App) -> io::Result<()> {
+    let mut calc = Calculator::new();
+    plot::register(&mut calc, &app.plot_requested);
+    table::register(&mut calc, &app.table_requested);
+    if let Some(msg) = app.autosave.restore(&mut calc) {
+        terminal.insert_before(1, |b| Paragraph::new(msg).render(b.area, b))?;
+    }
     loop {
         app.tokens = Scanner::new(&app.input).scan_tokens().unwrap();
-        interpreter.save_assignments = false;
-        let res = Parser::new(&app.tokens, &app.input)
-            .parse()
-            .and_then(|e| interpreter.visit_stmt_owned(e));
-        app.message = match res {
-            Ok(n) => format!("Current result {}", disp_num(&n, DISPLAY_DIGITS).unwrap()),
-            Err(e) => format!("{}", e),
+        app.message = if let Some(search) = &app.search {
+            let found = search.found.and_then(|i| app.history.get(i));
+            match found {
+                Some(input) => format!(
+                    "(reverse-i-search)`{}': {}",
+                    search.query,
+                    input.replace('\n', " ⏎ ")
+                ),
+                None if search.query.is_empty() => "(reverse-i-search)`'".to_string(),
+                None => format!("(failed reverse-i-search)`{}'", search.query),
+            }
+        } else if let Some(cmd) = app.input.strip_prefix(':') {
+            commands::hint(cmd)
+        } else if app.input.starts_with('?') {
+            "Help on a function or variable".to_string()
+        } else {
+            match calc.preview(&app.input) {
+                Ok(n) => format!("Current result {}", format_value(&n, &app.format)),
+                Err(e) => e.message,
+            }
         };
 
-        interpreter.save_assignments = true;
-        terminal.draw(|f| ui(f, &app, &interpreter))?;
+        if let Some(insp) = &mut app.inspector {
+            insp.refresh(&calc, &app.format);
+        }
+        let size = terminal.size()?;
+        if let Some(plot) = &mut app.plot {
+            // two points per column, as braille dots are two wide
+            plot.sample(&mut calc, size.width as usize * 2);
+        }
+        // plots and the inspector take over the screen, but for a line to print results through
+        let height = match app.plot.is_some() || app.inspector.is_some() {
+            true => size.height.saturating_sub(1).max(VIEWPORT_HEIGHT),
+            false => VIEWPORT_HEIGHT + input_lines(&app.input) - 1,
+        };
+        fit_viewport(terminal, height)?;
+        terminal.draw(|f| ui(f, &app))?;
 
         match event::read()? {
-            Event::Key(key) => {
-                if handle_key_event(key, terminal, &mut app, &mut interpreter)?.is_break() {
-                    break Ok(());
-                }
+            Event::Key(key) if handle_key_event(key, terminal, &mut app, &mut calc)?.is_break() => {
+                break app.autosave.after_eval(&calc);
             }
             Event::Resize(_, _) => terminal.autoresize()?,
             _ => (),
@@ -268,47 +833,179 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
     }
 }
 
-fn ui(f: &mut Frame, app: &App, interpreter: &Interpreter) {
+fn ui(f: &mut Frame, app: &App) {
+    if let Some(plot) = &app.plot {
+        return plot.render(f, f.size());
+    }
+    let Some(insp) = &app.inspector else {
+        return editor_ui(f, app, f.size());
+    };
+    let [main_area, side_area] =
+        Layout::horizontal([Constraint::Min(0), Constraint::Percentage(35)]).areas(f.size());
+    let [history_area, editor_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(VIEWPORT_HEIGHT + input_lines(&app.input) - 1),
+    ])
+    .areas(main_area);
+
+    // the latest inputs and results, bottom up
+    let mut lines = Vec::new();
+    for entry in app.history.iter().rev() {
+        if lines.len() >= history_area.height as usize {
+            break;
+        }
+        if let Some(res) = &entry.result {
+            lines.push(Line::styled(format!("= {res}"), Style::new().red()));
+        }
+        lines.extend(entry.input.rsplit('\n').map(Line::raw));
+    }
+    lines.truncate(history_area.height as usize);
+    lines.reverse();
+    let top = history_area.height.saturating_sub(lines.len() as u16);
+    f.render_widget(
+        Paragraph::new(lines),
+        Rect {
+            y: history_area.y + top,
+            height: history_area.height - top,
+            ..history_area
+        },
+    );
+
+    let title = match insp.focused {
+        true => " ↑↓ select · Enter insert · Tab back ",
+        false => " Tab to select · F2 to close ",
+    };
+    let list = List::new(insp.entries.iter().map(|e| {
+        let style = match e.builtin {
+            true => Style::new().dark_gray(),
+            false => Style::new(),
+        };
+        Line::styled(e.text.as_str(), style)
+    }))
+    .block(
+        Block::default()
+            .borders(Borders::LEFT)
+            .border_style(Style::new().dark_gray())
+            .title(Line::styled(title, Style::new().dark_gray())),
+    )
+    .highlight_style(match insp.focused {
+        true => Style::new().on_dark_gray(),
+        false => Style::new(),
+    });
+    f.render_stateful_widget(
+        list,
+        side_area,
+        &mut ListState::default().with_selected(Some(insp.selected)),
+    );
+
+    editor_ui(f, app, editor_area);
+}
+
+/// The message, input and completions, which are all there is without the inspector.
+fn editor_ui(f: &mut Frame, app: &App, area: Rect) {
     let vertical = Layout::vertical([
         Constraint::Length(2),
-        Constraint::Length(1),
+        Constraint::Length(input_lines(&app.input)),
         Constraint::Min(0),
     ]);
 
-    let [mut msg_area, input_area, mut completion_area] = vertical.areas(f.size());
+    let [mut msg_area, input_area, mut completion_area] = vertical.areas(area);
     if !app.message.is_empty() {
-        let msg = Paragraph::new(format!("{}", app.message)).block(
+        let msg = Paragraph::new(app.message.as_str()).block(
             Block::default()
                 .borders(Borders::ALL.difference(Borders::BOTTOM))
                 .border_set(border::ONE_EIGHTH_WIDE),
         );
-        msg_area.width = app.message.len() as u16 + 2;
+        msg_area.width = msg_area.width.min(width(&app.message) + 2);
 
         f.render_widget(msg, msg_area);
     }
     let input = Paragraph::new(app.input.as_str()).style(Style::default());
 
     f.render_widget(input, input_area);
+    if app.vi.as_ref().is_some_and(|vi| vi.mode == Mode::Normal) {
+        let mode = Paragraph::new("NORMAL").dark_gray().right_aligned();
+        f.render_widget(mode, input_area);
+    }
     let buf = f.buffer_mut();
-    color_tokens(buf, &app.tokens, input_area.x, input_area.y);
-
-    f.set_cursor(input_area.x + app.cursor_position as u16, input_area.y);
+    color_tokens(
+        buf,
+        &app.input,
+        &app.tokens,
+        Some(app.cursor_position),
+        input_area.x,
+        input_area.y,
+    );
+
+    let (col, row) = screen_pos(&app.input, app.cursor_position);
+    f.set_cursor(input_area.x + col, input_area.y + row);
 
     if let Some(comp) = &app.completion {
         let completions_list = List::new(
-            comp.completions
+            comp.candidates
                 .iter()
                 .take(completion_area.height as usize)
-                .map(|s| s.as_str()),
+                .map(|c| {
+                    let kind = if c.is_fn { "fn " } else { "var" };
+                    let detail = if c.is_fn { "" } else { c.detail.as_str() };
+                    Line::from(vec![
+                        Span::from(format!("{kind} ")).dark_gray(),
+                        Span::from(c.name.as_str()),
+                        Span::from(format!(" {detail}")).dark_gray(),
+                    ])
+                }),
         )
         .highlight_style(Style::default().on_dark_gray());
 
+        let mut side_area = completion_area;
         completion_area.height = completion_area.height.min(completions_list.len() as u16);
-        completion_area.width = 32;
+        completion_area.width = completion_area.width.min(COMPLETION_WIDTH);
         f.render_stateful_widget(
             completions_list,
             completion_area,
             &mut ListState::default().with_selected(Some(comp.index)),
         );
+
+        side_area.x += completion_area.width + 1;
+        side_area.width = side_area.width.saturating_sub(completion_area.width + 1);
+        let cand = &comp.candidates[comp.index];
+        let heading = match cand.is_fn {
+            true => cand.detail.clone(),
+            false => format!("{} = {}", cand.name, cand.detail),
+        };
+        let mut side = vec![Line::styled(heading, Style::new().bold())];
+        if let Some(help) = &cand.help {
+            side.push(Line::from(help.as_str()));
+        }
+        let side = Paragraph::new(side).wrap(Wrap { trim: true }).block(
+            Block::default()
+                .borders(Borders::LEFT)
+                .border_style(Style::new().dark_gray()),
+        );
+        f.render_widget(side, side_area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vi_escape() {
+        let mut app = App {
+            vi: Some(Vi::default()),
+            input: "1 + 2".to_string(),
+            cursor_position: 5,
+            ..Default::default()
+        };
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert!(app.handle_vi_key(press(KeyCode::Esc)));
+        // in normal mode Esc cancels the pending `d` instead of quitting
+        for code in [KeyCode::Char('0'), KeyCode::Char('d'), KeyCode::Esc] {
+            assert!(app.handle_vi_key(press(code)));
+        }
+        assert!(app.handle_vi_key(press(KeyCode::Char('w'))));
+        assert_eq!(app.input, "1 + 2");
+        assert_eq!(app.cursor_position, 2);
     }
 }
@@ -0,0 +1,232 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crossterm::event::KeyCode;
+use ferricalc::{
+    disp_num,
+    native::{Arity, NativeFn},
+    Calculator, EnvMember, Func, Value, PREC_BITS,
+};
+use ratatui::{prelude::*, symbols::Marker, widgets::*};
+use rug::Float;
+
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+];
+
+/// The plot asked for by the last `plot(...)` call, picked up once the input is evaluated.
+pub type Requested = Rc<RefCell<Option<Plot>>>;
+
+/// Defines `plot(f, ..., xmin, xmax)`, which hands its functions over to `requested`.
+pub fn register(calc: &mut Calculator, requested: &Requested) {
+    let requested = requested.clone();
+    let f = NativeFn::new(
+        "plot",
+        Arity::AtLeast(3),
+        "Draws functions of x between xmin and xmax",
+        move |_, args| {
+            let (funcs, range) = args.split_at(args.len() - 2);
+            let [xmin, xmax] = [&range[0], &range[1]].map(|v| v.clone().into_number());
+            let (xmin, xmax) = (xmin?.to_f64(), xmax?.to_f64());
+            if xmin.partial_cmp(&xmax) != Some(std::cmp::Ordering::Less) {
+                return Err("plot needs xmin < xmax".into());
+            }
+            let funcs = funcs
+                .iter()
+                .map(|f| f.clone().into_func())
+                .collect::<Result<Vec<_>, _>>()?;
+            *requested.borrow_mut() = Some(Plot::new(funcs, (xmin, xmax)));
+            Ok(Value::List(args[..args.len() - 2].to_vec()))
+        },
+    )
+    .with_params("f, ..., xmin, xmax");
+    calc.register(f);
+}
+
+/// Functions drawn over a range that can be zoomed and panned.
+pub struct Plot {
+    funcs: Vec<(Option<String>, Func)>,
+    x: (f64, f64),
+    y: (f64, f64),
+    initial: (f64, f64),
+    /// Continuous runs of points of each function, split where it isn't defined.
+    segments: Vec<Vec<Vec<(f64, f64)>>>,
+    /// Fit the y range to the points on the next sample.
+    fit_y: bool,
+}
+
+impl Plot {
+    fn new(funcs: Vec<Func>, x: (f64, f64)) -> Self {
+        Self {
+            funcs: funcs.into_iter().map(|f| (None, f)).collect(),
+            x,
+            y: (-1.0, 1.0),
+            initial: x,
+            segments: Vec::new(),
+            fit_y: true,
+        }
+    }
+
+    /// Evaluates every function at `n` points across the x range.
+    pub fn sample(&mut self, calc: &mut Calculator, n: usize) {
+        let n = n.max(2);
+        let (x0, x1) = self.x;
+        self.segments = self
+            .funcs
+            .iter_mut()
+            .map(|(label, f)| {
+                label.get_or_insert_with(|| label_of(f, calc));
+                let mut segments = vec![Vec::new()];
+                for i in 0..n {
+                    let x = x0 + (x1 - x0) * i as f64 / (n - 1) as f64;
+                    let arg = Value::Number(Float::with_val(PREC_BITS, x));
+                    match calc.call(f, vec![arg]).map(Value::into_number) {
+                        Ok(Ok(y)) if y.is_finite() => {
+                            segments.last_mut().unwrap().push((x, y.to_f64()))
+                        }
+                        _ if segments.last().unwrap().is_empty() => {}
+                        _ => segments.push(Vec::new()),
+                    }
+                }
+                segments
+            })
+            .collect();
+        if self.fit_y {
+            self.fit_y = false;
+            let ys = self.segments.iter().flatten().flatten().map(|&(_, y)| y);
+            let (lo, hi) = ys.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| {
+                (lo.min(y), hi.max(y))
+            });
+            self.y = match hi - lo {
+                d if d > 0.0 => (lo - d * 0.05, hi + d * 0.05),
+                0.0 => (lo - 1.0, hi + 1.0),
+                // nothing was defined
+                _ => (-1.0, 1.0),
+            };
+        }
+    }
+
+    /// Zooms or pans for `code`, after which the plot needs sampling again. Gives `false` when
+    /// the plot should be closed.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        let pan = |(a, b): (f64, f64), by: f64| (a + (b - a) * by, b + (b - a) * by);
+        let zoom = |(a, b): (f64, f64), by: f64| {
+            let (mid, half) = ((a + b) / 2.0, (b - a) / 2.0 * by);
+            (mid - half, mid + half)
+        };
+        match code {
+            KeyCode::Left | KeyCode::Char('h') => self.x = pan(self.x, -0.1),
+            KeyCode::Right | KeyCode::Char('l') => self.x = pan(self.x, 0.1),
+            KeyCode::Down | KeyCode::Char('j') => self.y = pan(self.y, -0.1),
+            KeyCode::Up | KeyCode::Char('k') => self.y = pan(self.y, 0.1),
+            KeyCode::Char('+' | '=') => (self.x, self.y) = (zoom(self.x, 0.8), zoom(self.y, 0.8)),
+            KeyCode::Char('-' | '_') => (self.x, self.y) = (zoom(self.x, 1.25), zoom(self.y, 1.25)),
+            KeyCode::Char('0') => {
+                self.x = self.initial;
+                self.fit_y = true;
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => return false,
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let mut datasets = Vec::new();
+        for (i, ((label, _), segments)) in self.funcs.iter().zip(&self.segments).enumerate() {
+            let style = Style::new().fg(COLORS[i % COLORS.len()]);
+            for (j, points) in segments.iter().enumerate() {
+                let dataset = Dataset::default()
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(style)
+                    .data(points);
+                datasets.push(match (j, label) {
+                    (0, Some(label)) => dataset.name(label.as_str()),
+                    _ => dataset,
+                });
+            }
+        }
+        let axis = |(a, b): (f64, f64)| {
+            Axis::default()
+                .style(Style::new().dark_gray())
+                .bounds([a, b])
+                .labels(
+                    [a, (a + b) / 2.0, b]
+                        .map(|v| Span::raw(axis_label(v)))
+                        .to_vec(),
+                )
+        };
+        let chart = Chart::new(datasets)
+            .block(
+                Block::default()
+                    .title(" ←→↑↓ pan · +- zoom · 0 reset · q close ")
+                    .title_style(Style::new().dark_gray()),
+            )
+            .x_axis(axis(self.x))
+            .y_axis(axis(self.y));
+        f.render_widget(chart, area);
+    }
+}
+
+fn axis_label(v: f64) -> String {
+    disp_num(&Float::with_val(PREC_BITS, v), 4).unwrap_or_default()
+}
+
+/// The name `f` is defined under, or else its source.
+pub fn label_of(f: &Func, calc: &Calculator) -> String {
+    if let Func::UserFn(u) = f {
+        let named = calc.env().iter().find(|(_, m)| match m {
+            EnvMember::Fn(Func::UserFn(g)) => Rc::ptr_eq(&g.expr, &u.expr),
+            _ => false,
+        });
+        if let Some((name, _)) = named {
+            return name.to_string();
+        }
+    }
+    f.source()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_and_zooms() {
+        let mut calc = Calculator::new();
+        let requested = Requested::default();
+        register(&mut calc, &requested);
+        calc.eval("f(x) = 1 / x").unwrap();
+        calc.eval("plot(f, x -> x^2, -1, 1)").unwrap();
+        let mut plot = requested.take().unwrap();
+        plot.sample(&mut calc, 5);
+        // 1/x isn't defined at 0, which splits it in two
+        assert_eq!(plot.segments[0].len(), 2);
+        assert_eq!(
+            plot.segments[1],
+            [[
+                (-1.0, 1.0),
+                (-0.5, 0.25),
+                (0.0, 0.0),
+                (0.5, 0.25),
+                (1.0, 1.0)
+            ]]
+        );
+        assert!(plot.y.0 < -2.0 && plot.y.1 > 2.0, "{:?}", plot.y);
+        let labels: Vec<_> = plot.funcs.iter().map(|(l, _)| l.clone().unwrap()).collect();
+        assert_eq!(labels, ["f", "(x -> x^2)"]);
+
+        assert!(plot.handle_key(KeyCode::Char('+')));
+        assert_eq!(plot.x, (-0.8, 0.8));
+        plot.handle_key(KeyCode::Char('0'));
+        assert_eq!(plot.x, (-1.0, 1.0));
+        assert!(!plot.handle_key(KeyCode::Char('q')));
+
+        assert!(calc.eval("plot(f, 1, 1)").is_err());
+        assert!(calc.eval("plot(2, 0, 1)").is_err());
+    }
+}
@@ -3,27 +3,39 @@ use std::ops::Range;
 
 use rug::{ops::CompleteRound, Float};
 
-use crate::{ast::Literal, PREC_BITS};
+use crate::interpreter::{ast::Literal, PREC_BITS};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenKind {
     LParen,
     RParen,
     Comma,
+    Semicolon,
+    /// A line break that ends a statement. Other line breaks are whitespace.
+    Newline,
     Dot,
     Minus,
     Plus,
     Slash,
     Star,
     Exp,
+    Arrow,
+    Sqrt,
+    /// `²`, `³`, `⁻¹`, … with the exponent as the literal.
+    Superscript,
     Indentifier,
+    Let,
+    In,
     Equal,
+    ColonEqual,
     Number,
+    Comment,
     Eof,
     Unkown,
 }
 
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum ScanError {
 }
@@ -47,6 +59,8 @@ pub struct Scanner<'a> {
     tokens: Vec<Token>,
     start: usize,
     current: usize,
+    /// Number of unclosed parentheses.
+    depth: usize,
 }
 
 impl <'a> Scanner <'a> {
@@ -56,6 +70,7 @@ impl <'a> Scanner <'a> {
             tokens: Vec::new(),
             start: 0,
             current: 0,
+            depth: 0,
         }
     }
     fn is_at_end(&self) -> bool {
@@ -86,19 +101,41 @@ impl <'a> Scanner <'a> {
     fn scan_token(&mut self) -> Result<(), String> {
         let c = self.advance();
         match c {
-            '(' => self.add_token(TokenKind::LParen, None),
-            ')' => self.add_token(TokenKind::RParen, None),
+            '(' => {
+                self.depth += 1;
+                self.add_token(TokenKind::LParen, None)
+            }
+            ')' => {
+                self.depth = self.depth.saturating_sub(1);
+                self.add_token(TokenKind::RParen, None)
+            }
+            '\n' if self.depth == 0 && self.ends_expression() => {
+                self.add_token(TokenKind::Newline, None)
+            }
             ',' => self.add_token(TokenKind::Comma, None),
+            ';' => self.add_token(TokenKind::Semicolon, None),
             '.' => self.add_token(TokenKind::Dot, None),
+            '-' if self.match_char('>') => self.add_token(TokenKind::Arrow, None),
             '-' => self.add_token(TokenKind::Minus, None),
             '+' => self.add_token(TokenKind::Plus, None),
+            '/' if self.match_char('/') => self.comment(),
             '/' => self.add_token(TokenKind::Slash, None),
-            '*' => self.add_token(TokenKind::Star, None),
+            '*' | '×' | '·' => self.add_token(TokenKind::Star, None),
+            '÷' => self.add_token(TokenKind::Slash, None),
+            '−' => self.add_token(TokenKind::Minus, None),
+            '√' => self.add_token(TokenKind::Sqrt, None),
+            c if c == '⁻' || superscript_digit(c).is_some() => self.superscript(),
             '=' => self.add_token(TokenKind::Equal, None),
+            ':' if self.match_char('=') => self.add_token(TokenKind::ColonEqual, None),
             '^' => self.add_token(TokenKind::Exp, None),
+            '#' => self.comment(),
+            '$' if self.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                self.advance_while(|c| c.is_ascii_digit());
+                self.add_token(TokenKind::Indentifier, None)
+            }
             c if c.is_ascii_digit() => self.number()?,
-            c if c.is_ascii_alphabetic() => self.literal(),
-            ' ' => {},
+            c if is_ident_start(c) => self.literal(),
+            c if c.is_whitespace() => {},
          _ => self.add_token(TokenKind::Unkown, None),
         };
         Ok(())
@@ -107,7 +144,7 @@ impl <'a> Scanner <'a> {
     where
         P: FnMut(char) -> bool,
     {
-        while self.peek().is_some_and(|c| predicate(c)) {
+        while self.peek().is_some_and(&mut predicate) {
             self.advance();
         }
     }
@@ -118,6 +155,19 @@ impl <'a> Scanner <'a> {
             self.advance();
             self.advance_while(|c| c.is_ascii_digit());
         }
+        // `2e-3`, but not `2e` or `2*e`, as `e` is also a constant
+        let sign_len = match self.peek_offset(1) {
+            Some('+' | '-') => 1,
+            _ => 0,
+        };
+        if matches!(self.peek(), Some('e' | 'E'))
+            && self
+                .peek_offset(1 + sign_len)
+                .is_some_and(|c| c.is_ascii_digit())
+        {
+            self.current += 1 + sign_len;
+            self.advance_while(|c| c.is_ascii_digit());
+        }
 
         self.add_token(
             TokenKind::Number,
@@ -129,19 +179,89 @@ impl <'a> Scanner <'a> {
         Ok(())
     }
     fn literal(&mut self) {
-        self.advance_while(|c| c.is_ascii_alphanumeric());
-        self.add_token(TokenKind::Indentifier, None)
+        self.advance_while(is_ident_char);
+        let kind = match &self.source[self.start..self.current] {
+            "let" => TokenKind::Let,
+            "in" => TokenKind::In,
+            _ => TokenKind::Indentifier,
+        };
+        self.add_token(kind, None)
+    }
+    fn superscript(&mut self) {
+        self.advance_while(|c| superscript_digit(c).is_some());
+        let exponent: String = self.source[self.start..self.current]
+            .chars()
+            .map(|c| superscript_digit(c).unwrap_or('-'))
+            .collect();
+        match Float::parse(&exponent) {
+            Ok(n) => self.add_token(
+                TokenKind::Superscript,
+                Some(Literal::Number(n.complete(PREC_BITS))),
+            ),
+            // a lone `⁻`
+            Err(_) => self.add_token(TokenKind::Unkown, None),
+        }
+    }
+    /// Comments run to the end of the line. They are kept as tokens for highlighting, but the
+    /// parser never sees them.
+    fn comment(&mut self) {
+        self.advance_while(|c| c != '\n');
+        self.add_token(TokenKind::Comment, None)
+    }
+    /// Whether the tokens so far could be a whole statement, so that a line break after them
+    /// starts a new one.
+    fn ends_expression(&self) -> bool {
+        self.tokens
+            .iter()
+            .rev()
+            .find(|t| t.kind != TokenKind::Comment)
+            .is_some_and(|t| {
+                matches!(
+                    t.kind,
+                    TokenKind::Number
+                        | TokenKind::Indentifier
+                        | TokenKind::RParen
+                        | TokenKind::Superscript
+                )
+            })
+    }
+    fn match_char(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.advance();
+            return true;
+        }
+        false
     }
     fn advance(&mut self) -> char {
         let c = self.source[self.current..].chars().next().unwrap();
-        self.current += 1;
+        self.current += c.len_utf8();
         c
     }
     fn peek(&self) -> Option<char> {
         self.source[self.current..].chars().next()
     }
     fn peek_offset(&self, offset: usize) -> Option<char> {
-        self.source[self.current + offset..].chars().next()
+        self.source.get(self.current + offset..)?.chars().next()
     }
     
 }
+
+pub fn is_ident_start(c: char) -> bool {
+    (c.is_alphabetic() || c == '_') && superscript_digit(c).is_none()
+}
+
+pub fn is_ident_char(c: char) -> bool {
+    (c.is_alphanumeric() || c == '_') && superscript_digit(c).is_none()
+}
+
+/// The digit a superscript digit stands for.
+fn superscript_digit(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁰' as u32, 10),
+        _ => None,
+    }
+}
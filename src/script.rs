@@ -0,0 +1,259 @@
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, BufRead},
+    process::ExitCode,
+};
+
+use ferricalc::{disp_value, Calculator, Error, Scanner, TokenKind, Value, DISPLAY_DIGITS};
+
+use crate::editor::unclosed_parens;
+
+pub const USAGE: &str = "\
+usage: ferricalc                         start the interactive calculator
+       ferricalc run [--quiet] <file>    evaluate a script, printing each result
+       ferricalc -e <expr> [-e <expr>]   evaluate expressions
+       <command> | ferricalc             evaluate each line of stdin";
+
+/// An error in a script, located by 1-based line and column.
+#[derive(Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ScriptError {
+    /// Locates `e` in `stmt`, which starts on line `line_no` and may span several.
+    fn new(line_no: usize, stmt: &str, e: Error) -> Self {
+        let before = &stmt[..e.offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: line_no + before.matches('\n').count(),
+            column: before[line_start..].chars().count() + 1,
+            message: e.message,
+        }
+    }
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Evaluates one line of input, or returns `None` if there is nothing but comments on it.
+pub fn eval_line(calc: &mut Calculator, line: &str) -> Option<Result<Value, Error>> {
+    let tokens = Scanner::new(line).scan_tokens().unwrap();
+    if tokens
+        .iter()
+        .all(|t| matches!(t.kind, TokenKind::Comment | TokenKind::Eof))
+    {
+        return None;
+    }
+    Some(calc.eval(line))
+}
+
+/// Evaluates `source` line by line, passing each result to `on_result`. Lines with unclosed
+/// parentheses continue on the next one. Blank and comment-only lines are skipped, and
+/// evaluation stops at the first error.
+pub fn run_source(
+    calc: &mut Calculator,
+    source: &str,
+    mut on_result: impl FnMut(&Value),
+) -> Result<(), ScriptError> {
+    let mut stmt = String::new();
+    let mut start = 0;
+    let mut lines = source.lines().enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        if stmt.is_empty() {
+            start = i;
+        } else {
+            stmt.push('\n');
+        }
+        stmt += line;
+        let tokens = Scanner::new(&stmt).scan_tokens().unwrap();
+        if unclosed_parens(&tokens) > 0 && lines.peek().is_some() {
+            continue;
+        }
+        match eval_line(calc, &stmt) {
+            Some(Ok(res)) => on_result(&res),
+            Some(Err(e)) => return Err(ScriptError::new(start + 1, &stmt, e)),
+            None => {}
+        }
+        stmt.clear();
+    }
+    Ok(())
+}
+
+/// Entry point for `ferricalc run`, `args` being everything after `run`.
+pub fn run_cli(args: &[String]) -> ExitCode {
+    let mut quiet = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "-q" | "--quiet" => quiet = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("ferricalc: {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut calc = Calculator::new();
+    let mut last = None;
+    let res = run_source(&mut calc, &source, |res| {
+        if quiet {
+            last = Some(res.clone());
+        } else {
+            println!("{}", disp_value(res, DISPLAY_DIGITS));
+        }
+    });
+    match res {
+        Ok(()) => {
+            if let Some(last) = last {
+                println!("{}", disp_value(&last, DISPLAY_DIGITS));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{path}:{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Entry point for `ferricalc -e <expr>...`.
+pub fn eval_cli(args: &[String]) -> ExitCode {
+    let mut exprs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("-e" | "--eval", Some(expr)) => exprs.push(expr),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let mut calc = Calculator::new();
+    for expr in exprs {
+        match eval_line(&mut calc, expr) {
+            Some(Ok(res)) => println!("{}", disp_value(&res, DISPLAY_DIGITS)),
+            Some(Err(e)) => {
+                eprintln!("-e:{}", ScriptError::new(1, expr, e));
+                return ExitCode::FAILURE;
+            }
+            None => {}
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Evaluates stdin line by line as it arrives. Unlike scripts, errors are reported and
+/// evaluation carries on, but the exit code still reflects them.
+pub fn run_stdin() -> ExitCode {
+    let mut calc = Calculator::new();
+    let mut failed = false;
+    let mut stmt = String::new();
+    let mut start = 0;
+    let mut lines = io::stdin().lock().lines().enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("ferricalc: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if stmt.is_empty() {
+            start = i;
+        } else {
+            stmt.push('\n');
+        }
+        stmt += &line;
+        let tokens = Scanner::new(&stmt).scan_tokens().unwrap();
+        if unclosed_parens(&tokens) > 0 && lines.peek().is_some() {
+            continue;
+        }
+        match eval_line(&mut calc, &stmt) {
+            Some(Ok(res)) => println!("{}", disp_value(&res, DISPLAY_DIGITS)),
+            Some(Err(e)) => {
+                eprintln!("<stdin>:{}", ScriptError::new(start + 1, &stmt, e));
+                failed = true;
+            }
+            None => {}
+        }
+        stmt.clear();
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> (Vec<String>, Result<(), ScriptError>) {
+        let mut results = Vec::new();
+        let res = run_source(&mut Calculator::new(), source, |v| {
+            results.push(disp_value(v, DISPLAY_DIGITS))
+        });
+        (results, res)
+    }
+
+    #[test]
+    fn runs_each_line() {
+        let (results, res) = run("# radius\nr = 2\n\nf(x) = x * r\nf(3); ans + 1\n");
+        assert_eq!(res, Ok(()));
+        assert_eq!(results, ["2", "<fn(x) uses r>", "7"]);
+    }
+
+    #[test]
+    fn reports_error_location() {
+        let (results, res) = run("a = 1\nb = 2; c = a + d\ne = 3\n");
+        assert_eq!(results, ["1"]);
+        assert_eq!(
+            res,
+            Err(ScriptError {
+                line: 2,
+                column: 16,
+                message: "Undeclared variable 'd'".to_string()
+            })
+        );
+        let (_, res) = run("f(x) = x + y\n\n2 * f(1)");
+        assert_eq!(res.map_err(|e| (e.line, e.column)), Err((3, 5)));
+        let (_, res) = run("1 + (2");
+        assert_eq!(res.map_err(|e| (e.line, e.column)), Err((1, 7)));
+    }
+
+    #[test]
+    fn continues_unclosed_parens() {
+        let (results, res) = run("f(x) = (\n  x^2 # square\n  + 1\n)\nf(2)\n(1 +\n  * 2)\n");
+        assert_eq!(results, ["<fn(x)>", "5"]);
+        assert_eq!(res.map_err(|e| (e.line, e.column)), Err((7, 3)));
+    }
+
+    #[test]
+    fn skips_comment_only_lines() {
+        let mut i = Calculator::new();
+        assert!(eval_line(&mut i, "  // nothing here").is_none());
+        assert!(eval_line(&mut i, "").is_none());
+        assert!(eval_line(&mut i, "1 # one").is_some_and(|r| r.is_ok()));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use ferricalc::Calculator;
+
+use crate::script::run_source;
+
+/// `$XDG_DATA_HOME/ferricalc`, falling back to `~/.local/share/ferricalc`.
+pub fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".local/share")))
+        .map(|d| d.join("ferricalc"))
+}
+
+pub fn data_file(name: &str) -> Result<PathBuf, String> {
+    data_dir()
+        .map(|d| d.join(name))
+        .ok_or("Can't find a data directory, set $XDG_DATA_HOME or $HOME".to_string())
+}
+
+pub fn default_session_file() -> Result<PathBuf, String> {
+    data_file("session.fc")
+}
+
+/// Writes every user definition to `path` as statements.
+pub fn save(calc: &Calculator, path: &Path) -> io::Result<usize> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let source = calc.export();
+    fs::write(path, format!("# ferricalc session\n{source}"))?;
+    Ok(source.lines().count())
+}
+
+/// Evaluates a file written by [`save`], or any other script. Its statements aren't numbered
+/// as results, so `$1` stays the first result typed in.
+pub fn load(calc: &mut Calculator, path: &Path) -> Result<usize, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let results = calc.results().len();
+    let mut count = 0;
+    let res = run_source(calc, &source, |_| count += 1);
+    calc.truncate_results(results);
+    res.map_err(|e| format!("{}:{e}", path.display()))?;
+    Ok(count)
+}
+
+/// Auto-saving writes the session to a file in the data dir after every evaluation. The file
+/// existing is what turns it on, so a saved session is restored on the next start.
+#[derive(Default)]
+pub struct AutoSave {
+    pub enabled: bool,
+}
+
+impl AutoSave {
+    fn path() -> Result<PathBuf, String> {
+        data_file("autosave.fc")
+    }
+
+    /// Loads the auto-saved session if there is one, returning a message saying so.
+    pub fn restore(&mut self, calc: &mut Calculator) -> Option<String> {
+        let path = Self::path().ok().filter(|p| p.exists())?;
+        self.enabled = true;
+        Some(match load(calc, &path) {
+            Ok(n) => format!("Restored {n} definitions from {}", path.display()),
+            Err(e) => format!("Failed to restore session: {e}"),
+        })
+    }
+
+    pub fn set_enabled(&mut self, calc: &Calculator, enabled: bool) -> Result<String, String> {
+        let path = Self::path()?;
+        self.enabled = enabled;
+        if enabled {
+            save(calc, &path).map_err(|e| e.to_string())?;
+            Ok(format!("Auto-saving to {}", path.display()))
+        } else {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+                _ => Ok("Auto-save off".to_string()),
+            }
+        }
+    }
+
+    pub fn after_eval(&self, calc: &Calculator) -> io::Result<()> {
+        match Self::path() {
+            Ok(path) if self.enabled => save(calc, &path).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use ferricalc::{disp_value, DISPLAY_DIGITS};
+
+    use super::*;
+
+    #[test]
+    fn load_keeps_results() {
+        let path = env::temp_dir().join(format!("ferricalc-session-{}.fc", process::id()));
+        let mut calc = Calculator::new();
+        for line in ["a = 2", "f(x) = x * a", "f(3)"] {
+            calc.eval(line).unwrap();
+        }
+        save(&calc, &path).unwrap();
+
+        let mut restored = Calculator::new();
+        let loaded = load(&mut restored, &path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Ok(2));
+        assert!(restored.results().is_empty());
+        restored.eval("f(5)").unwrap();
+        let res = restored.eval("$1 + 1").unwrap();
+        assert_eq!(disp_value(&res, DISPLAY_DIGITS), "11");
+    }
+}
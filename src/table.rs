@@ -0,0 +1,234 @@
+use std::{cell::RefCell, rc::Rc};
+
+use ferricalc::{
+    disp_num, format_num,
+    native::{Arity, NativeFn},
+    Calculator, Doc, Format, Func, Value, PREC_BITS,
+};
+use rug::Float;
+
+use crate::plot::label_of;
+
+/// More rows than anyone would read in a terminal.
+const MAX_ROWS: usize = 1000;
+
+/// The table asked for by the last `table(...)` call, picked up once the input is evaluated.
+pub type Requested = Rc<RefCell<Option<Table>>>;
+
+/// Defines `table(f, ..., from, to, step)`, which hands its functions over to `requested`.
+pub fn register(calc: &mut Calculator, requested: &Requested) {
+    let requested = requested.clone();
+    let f = NativeFn::new(
+        "table",
+        Arity::AtLeast(4),
+        "Tabulates functions of x from one value to another",
+        move |_, args| {
+            let (funcs, range) = args.split_at(args.len() - 3);
+            let [from, to, step] =
+                [&range[0], &range[1], &range[2]].map(|v| v.clone().into_number());
+            let (from, to, step) = (from?, to?, step?);
+            let rows = Float::with_val(PREC_BITS, &to - &from) / &step;
+            if step.is_zero() || !rows.is_finite() || rows.is_sign_negative() {
+                return Err("table needs a step that goes from 'from' to 'to'".into());
+            }
+            // (0.7 - 0.1) / 0.2 comes out a hair under 3, which would drop the last row
+            let nearest = rows.clone().round();
+            let error = Float::with_val(PREC_BITS, &rows - &nearest).abs();
+            let steps = if error <= nearest.clone() * 1e-30 {
+                nearest
+            } else {
+                rows.floor()
+            };
+            let rows = (steps < MAX_ROWS)
+                .then(|| steps.to_f64() as usize)
+                .and_then(|n| n.checked_add(1))
+                .ok_or(format!("table would have more than {MAX_ROWS} rows"))?;
+            let funcs = funcs
+                .iter()
+                .map(|f| f.clone().into_func())
+                .collect::<Result<Vec<_>, _>>()?;
+            *requested.borrow_mut() = Some(Table {
+                funcs,
+                xs: (0..rows)
+                    .map(|i| Float::with_val(PREC_BITS, i * &step) + &from)
+                    .collect(),
+            });
+            Ok(Value::List(args[..args.len() - 3].to_vec()))
+        },
+    )
+    .with_params("f, ..., from, to, step")
+    .with_doc(Doc {
+        examples: &["table(sqrt, 1, 2, 0.25)"],
+        domain: None,
+    });
+    calc.register(f);
+}
+
+/// Functions to evaluate at each x.
+pub struct Table {
+    funcs: Vec<Func>,
+    xs: Vec<Float>,
+}
+
+impl Table {
+    pub fn compute(self, calc: &mut Calculator) -> Values {
+        let mut header = vec!["x".to_string()];
+        header.extend(self.funcs.iter().map(|f| label_of(f, calc)));
+        let rows = self
+            .xs
+            .into_iter()
+            .map(|x| {
+                let ys: Vec<_> = self
+                    .funcs
+                    .iter()
+                    .map(|f| {
+                        let y = calc.call(f, vec![Value::Number(x.clone())]);
+                        y.ok()
+                            .and_then(|y| y.into_number().ok())
+                            .filter(|y| y.is_finite())
+                    })
+                    .collect();
+                (x, ys)
+            })
+            .collect();
+        Values { header, rows }
+    }
+}
+
+/// A computed table. Cells are empty where a function isn't defined or doesn't give a number.
+pub struct Values {
+    header: Vec<String>,
+    rows: Vec<(Float, Vec<Option<Float>>)>,
+}
+
+impl Values {
+    fn cells(&self, num: impl Fn(&Float) -> String) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .map(|(x, ys)| {
+                let mut row = vec![num(x)];
+                row.extend(ys.iter().map(|y| y.as_ref().map(&num).unwrap_or_default()));
+                row
+            })
+            .collect()
+    }
+
+    /// The table as text, with each column lined up on the decimal point.
+    pub fn lines(&self, format: &Format) -> Vec<String> {
+        let rows = self.cells(|n| format_num(n, format));
+        let columns: Vec<Vec<String>> = (0..self.header.len())
+            .map(|c| align(rows.iter().map(|r| r[c].as_str()), format.decimal))
+            .collect();
+        let widths: Vec<_> = self
+            .header
+            .iter()
+            .zip(&columns)
+            .map(|(h, col)| {
+                col.iter()
+                    .chain([h])
+                    .map(|s| s.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |cells: Vec<&str>| {
+            let padded: Vec<_> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(s, &w)| format!("{s:<w$}"))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+        let mut lines = vec![line(self.header.iter().map(String::as_str).collect())];
+        lines.push(
+            widths
+                .iter()
+                .map(|&w| "─".repeat(w))
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+        for r in 0..rows.len() {
+            lines.push(line(columns.iter().map(|col| col[r].as_str()).collect()));
+        }
+        lines
+    }
+
+    /// The table as CSV, with plain numbers whatever the format.
+    pub fn csv(&self, digits: usize) -> String {
+        let mut csv = self
+            .header
+            .iter()
+            .map(|h| csv_field(h))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+        for row in self.cells(|n| disp_num(n, digits).unwrap_or_default()) {
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Pads numbers so their decimal points, or where they would be, line up.
+fn align<'a>(cells: impl Iterator<Item = &'a str>, decimal: char) -> Vec<String> {
+    let cells: Vec<_> = cells.collect();
+    let point = |s: &str| s.find([decimal, 'e']).unwrap_or(s.len());
+    let before = cells.iter().map(|s| point(s)).max().unwrap_or(0);
+    cells
+        .iter()
+        .map(|s| format!("{}{s}", " ".repeat(before - point(s))))
+        .collect()
+}
+
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabulates() {
+        let mut calc = Calculator::new();
+        let requested = Requested::default();
+        register(&mut calc, &requested);
+        calc.eval("f(x) = 10 / x").unwrap();
+        calc.eval("table(f, x -> max(x, 1), -0.5, 1, 0.5)").unwrap();
+        let values = requested.take().unwrap().compute(&mut calc);
+        assert_eq!(
+            values.lines(&Format {
+                digits: 6,
+                ..Format::default()
+            }),
+            [
+                "x     f    (x -> max(x, 1))",
+                "────  ───  ────────────────",
+                "-0.5  -20  1",
+                " 0         1",
+                " 0.5   20  1",
+                " 1     10  1",
+            ]
+        );
+        assert_eq!(
+            values.csv(6),
+            "x,f,\"(x -> max(x, 1))\"\n-0.5,-20,1\n0,,1\n0.5,20,1\n1,10,1\n"
+        );
+
+        assert!(calc.eval("table(f, 0, 1, -1)").is_err());
+        assert!(calc.eval("table(f, 0, 1, 0)").is_err());
+        assert!(calc.eval("table(f, 0, 1, 0.0001)").is_err());
+        assert!(calc.eval("table(sqrt, 0, 1e300, 1)").is_err());
+
+        calc.eval("table(f, 0.1, 0.7, 0.2)").unwrap();
+        let values = requested.take().unwrap().compute(&mut calc);
+        assert_eq!(
+            values.csv(6),
+            "x,f\n0.1,100\n0.3,33.3333\n0.5,20\n0.7,14.2857\n"
+        );
+    }
+}
@@ -1,8 +1,13 @@
-use std::ops::Range;
+use std::{fmt::Display, str::FromStr};
 
-use rug::Float;
+use rug::{ops::Pow, Float};
+
+use crate::interpreter::{env::Func, free_vars::FreeVars, value::Value, PREC_BITS};
 
 pub const DISPLAY_DIGITS: usize = 32;
+/// Digits the floats hold, any more would only show rounding noise.
+pub const MAX_DIGITS: usize = (PREC_BITS as f64 * std::f64::consts::LOG10_2) as usize;
+
 fn insert_delimeter(str: &str, i: usize) -> String {
     let (l, r) = str.split_at(i);
     //println!("{str}, {l}, {r}, e:{exp}");
@@ -37,23 +42,220 @@ pub fn disp_num(num: &Float, digits: usize) -> Option<String> {
     Some(format!("{}{s}", if sign { "-" } else { "" }))
 }
 
-pub fn get_ident_at_end<'a>(input: &'a str) -> Option<&'a str> {
-    get_ident_range(input, input.len()).map(|r| &input[r])
+/// Formats `num` with enough digits to be read back exactly.
+pub fn exact_num(num: &Float) -> String {
+    if num.is_nan() {
+        return "0/0".to_string();
+    }
+    if num.is_infinite() {
+        return if num.is_sign_negative() {
+            "-1/0"
+        } else {
+            "1/0"
+        }
+        .to_string();
+    }
+    let s = num.to_string_radix(10, None);
+    let (mantissa, exp) = match s.split_once('e') {
+        Some((m, e)) => (m, Some(e)),
+        None => (s.as_str(), None),
+    };
+    let mantissa = match mantissa.contains('.') {
+        true => mantissa.trim_end_matches('0').trim_end_matches('.'),
+        false => mantissa,
+    };
+    match exp {
+        Some(exp) => format!("{mantissa}e{exp}"),
+        None => mantissa.to_string(),
+    }
+}
+
+/// How numbers are written out.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Notation {
+    /// Plain numbers, switching to scientific notation when they get too long.
+    #[default]
+    Auto,
+    /// A fixed number of decimals.
+    Fixed(usize),
+    Scientific,
+    /// Scientific notation with the exponent a multiple of 3.
+    Engineering,
+    /// Engineering notation with SI prefixes instead of exponents, like `4.7k`.
+    Si,
 }
-pub fn get_ident_range(input: &str, ident_end: usize) -> Option<Range<usize>> {
-    let mut last_char_index = None;
-    for (i, c) in input[..ident_end].char_indices().rev() {
-        if c.is_alphabetic() {
-            last_char_index = Some(i);
+
+impl FromStr for Notation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        Ok(match (words.next(), words.next()) {
+            (Some("auto"), None) => Notation::Auto,
+            (Some("fixed"), None) => Notation::Fixed(2),
+            (Some("fixed"), Some(n)) => match n.parse() {
+                Ok(decimals) if decimals <= MAX_DIGITS => Notation::Fixed(decimals),
+                _ => return Err(format!("fixed takes 0 to {MAX_DIGITS} decimals, not '{n}'")),
+            },
+            (Some("sci"), None) => Notation::Scientific,
+            (Some("eng"), None) => Notation::Engineering,
+            (Some("si"), None) => Notation::Si,
+            _ => {
+                return Err(format!(
+                    "Formats are auto, fixed [decimals], sci, eng and si, not '{s}'"
+                ))
+            }
+        })
+    }
+}
+
+impl Display for Notation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notation::Auto => write!(f, "auto"),
+            Notation::Fixed(n) => write!(f, "fixed {n}"),
+            Notation::Scientific => write!(f, "sci"),
+            Notation::Engineering => write!(f, "eng"),
+            Notation::Si => write!(f, "si"),
         }
-        if !c.is_alphanumeric() {
-            break;
+    }
+}
+
+/// Everything about how results are shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Format {
+    pub notation: Notation,
+    /// Significant digits, in every notation but fixed.
+    pub digits: usize,
+    /// Put between groups of three digits before the decimal point, as in `1_000_000`.
+    pub grouping: Option<char>,
+    pub decimal: char,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            notation: Notation::Auto,
+            digits: DISPLAY_DIGITS,
+            grouping: None,
+            decimal: '.',
         }
     }
-    last_char_index.map(|i| i..ident_end)
 }
 
+/// From 10^-24 to 10^24.
+const SI_PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
 
+/// Writes `num` out as `format` says.
+pub fn format_num(num: &Float, format: &Format) -> String {
+    let digits = format.digits.max(1);
+    let s = match format.notation {
+        _ if !num.is_finite() => disp_num(num, digits).unwrap(),
+        Notation::Fixed(decimals) => {
+            let decimals = decimals.min(MAX_DIGITS);
+            let scale = Float::with_val(num.prec(), 10).pow(decimals as u32);
+            let int = Float::with_val(num.prec(), num * scale)
+                .round()
+                .to_integer()
+                .unwrap();
+            let s = format!("{:0>width$}", int.clone().abs(), width = decimals + 1);
+            let (i, f) = s.split_at(s.len() - decimals);
+            let sign = if int < 0 { "-" } else { "" };
+            match decimals {
+                0 => format!("{sign}{i}"),
+                _ => format!("{sign}{i}.{f}"),
+            }
+        }
+        Notation::Auto => disp_num(num, digits).unwrap(),
+        _ if num.is_zero() => "0".to_string(),
+        notation => {
+            let (sign, mut s, exp) = num.to_sign_string_exp(10, Some(digits));
+            let exp = exp.unwrap() - 1;
+            let sign = if sign { "-" } else { "" };
+            let exp3 = match notation {
+                Notation::Scientific => exp,
+                _ => exp.div_euclid(3) * 3,
+            };
+            let int_len = (exp - exp3 + 1) as usize;
+            // engineering notation can have more digits before the point than were asked for
+            while s.len() < int_len {
+                s.push('0');
+            }
+            let mantissa = insert_delimeter(&s, int_len);
+            let prefix = usize::try_from(exp3 / 3 + 8)
+                .ok()
+                .and_then(|i| SI_PREFIXES.get(i));
+            match (notation, prefix) {
+                (Notation::Si, Some(prefix)) => format!("{sign}{mantissa}{prefix}"),
+                _ => format!("{sign}{mantissa}e{exp3}"),
+            }
+        }
+    };
+    localize(&s, format)
+}
+
+/// Groups the digits before the decimal point, and swaps in the decimal separator.
+fn localize(s: &str, format: &Format) -> String {
+    let start = s.starts_with('-') as usize;
+    let end = s[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(s.len(), |i| start + i);
+    let mut out = s[..start].to_string();
+    let int = &s[start..end];
+    for (i, c) in int.chars().enumerate() {
+        match format.grouping {
+            Some(sep) if i > 0 && (int.len() - i).is_multiple_of(3) => out.push(sep),
+            _ => {}
+        }
+        out.push(c);
+    }
+    out + &s[end..].replacen('.', &format.decimal.to_string(), 1)
+}
+
+pub fn disp_value(value: &Value, digits: usize) -> String {
+    format_value(
+        value,
+        &Format {
+            digits,
+            ..Format::default()
+        },
+    )
+}
+
+/// Like [`disp_value`], with numbers written out as `format` says.
+pub fn format_value(value: &Value, format: &Format) -> String {
+    match value {
+        Value::Number(n) => format_num(n, format),
+        Value::List(l) => format!(
+            "[{}]",
+            l.iter()
+                .map(|v| format_value(v, format))
+                .collect::<Vec<_>>()
+                // 1,5, 2,5 would be ambiguous
+                .join(if format.decimal == ',' { "; " } else { ", " })
+        ),
+        Value::Fn(Func::UserFn(f)) => {
+            let free: Vec<_> = FreeVars::of(&f.arguments, &f.expr)
+                .vars
+                .into_iter()
+                .filter(|v| !f.captures.contains_key(v))
+                .collect();
+            let mut captured: Vec<_> = f.captures.keys().map(String::as_str).collect();
+            captured.sort();
+            let mut s = format!("<fn({})", f.arguments.join(", "));
+            if !free.is_empty() {
+                s.push_str(&format!(" uses {}", free.join(", ")));
+            }
+            if !captured.is_empty() {
+                s.push_str(&format!(" captures {}", captured.join(", ")));
+            }
+            s.push('>');
+            s
+        }
+        Value::Fn(_) => "<builtin fn>".to_string(),
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -83,9 +285,72 @@ mod tests {
     }
 
     #[test]
-    fn test_get_ident_name() {
-        assert_eq!(get_ident_at_end("1abc"), Some("abc"));
-        assert_eq!(get_ident_at_end("abc+bob1bob1"), Some("bob1bob1"));
-        assert_eq!(get_ident_at_end("abc "), None)
+    fn formats() {
+        let num = |s: &str| Float::parse(s).unwrap().complete(256);
+        let fmt = |s: &str, notation: &str| {
+            let notation = notation.parse().unwrap();
+            let format = Format {
+                notation,
+                digits: 4,
+                ..Format::default()
+            };
+            format_num(&num(s), &format)
+        };
+        assert_eq!(fmt("3.14159", "fixed 2"), "3.14");
+        assert_eq!(fmt("-0.001", "fixed 2"), "0.00");
+        assert_eq!(fmt("-2.5", "fixed 0"), "-3");
+        assert_eq!(fmt("0.05", "fixed 3"), "0.050");
+        assert_eq!(fmt("12346", "sci"), "1.235e4");
+        assert_eq!(fmt("-0.00012", "sci"), "-1.2e-4");
+        let format = Format {
+            notation: Notation::Engineering,
+            digits: 1,
+            ..Format::default()
+        };
+        assert_eq!(format_num(&num("0.00047"), &format), "500e-6");
+        assert_eq!(fmt("12346", "eng"), "12.35e3");
+        assert_eq!(fmt("0.00047", "eng"), "470e-6");
+        assert_eq!(fmt("4700", "si"), "4.7k");
+        assert_eq!(fmt("0.0000022", "si"), "2.2µ");
+        assert_eq!(fmt("1e30", "si"), "1e30");
+        assert_eq!(fmt("0", "si"), "0");
+        assert!("fixed x".parse::<Notation>().is_err());
+        assert!("fixed 5000000000".parse::<Notation>().is_err());
+        let format = Format {
+            notation: Notation::Fixed(usize::MAX),
+            ..Format::default()
+        };
+        assert_eq!(format_num(&num("0.5"), &format).len(), MAX_DIGITS + 2);
+
+        let format = Format {
+            grouping: Some(','),
+            ..Format::default()
+        };
+        assert_eq!(format_num(&num("-1234567.5"), &format), "-1,234,567.5");
+        let format = Format {
+            grouping: Some('_'),
+            decimal: ',',
+            ..Format::default()
+        };
+        assert_eq!(format_num(&num("1234.5"), &format), "1_234,5");
+        assert_eq!(format_num(&num("123"), &format), "123");
+    }
+
+    #[test]
+    fn exact_num_round_trips() {
+        for num in ["1", "-2.5", "0.1", "12345.678", "1e-30", "1e310", "0"] {
+            let f = Float::parse(num).unwrap().complete(256);
+            let printed = exact_num(&f);
+            assert_eq!(
+                Float::parse(&printed).unwrap().complete(256),
+                f,
+                "{printed}"
+            );
+        }
+        assert_eq!(
+            exact_num(&Float::parse("-2.5").unwrap().complete(256)),
+            "-2.5"
+        );
+        assert_eq!(exact_num(&Float::with_val(256, f64::NEG_INFINITY)), "-1/0");
     }
 }
@@ -0,0 +1,247 @@
+use std::ops::Range;
+
+use crate::editor::{next_grapheme, prev_grapheme};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    /// `w`
+    NextWord,
+    /// `b`
+    PrevWord,
+    /// `e`
+    WordEnd,
+    Start,
+    End,
+    /// The whole input, for `dd` and `cc`.
+    Line,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    Move(Motion),
+    Delete(Motion),
+    /// Deletes and switches to insert mode.
+    Change(Motion),
+    /// Moves, if given a motion, and switches to insert mode.
+    Insert(Option<Motion>),
+    Paste,
+    Undo,
+    HistoryPrev,
+    HistoryNext,
+    Repeat,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Mode {
+    #[default]
+    Insert,
+    Normal,
+}
+
+/// State of the modal editor, which starts out in insert mode.
+#[derive(Default)]
+pub struct Vi {
+    pub mode: Mode,
+    /// An operator waiting for its motion.
+    pending: Option<char>,
+    /// The last change and the text typed after it, for `.`.
+    pub last_change: Option<(Command, String)>,
+    /// Whether text typed in insert mode belongs to `last_change`.
+    recording: bool,
+}
+
+impl Vi {
+    /// Translates a key pressed in normal mode.
+    pub fn normal_key(&mut self, c: char) -> Option<Command> {
+        let motion = match c {
+            'h' => Some(Motion::Left),
+            'l' | ' ' => Some(Motion::Right),
+            'w' => Some(Motion::NextWord),
+            'b' => Some(Motion::PrevWord),
+            'e' => Some(Motion::WordEnd),
+            '0' | '^' => Some(Motion::Start),
+            '$' => Some(Motion::End),
+            _ => None,
+        };
+        let cmd = match (self.pending.take(), motion, c) {
+            (Some('d'), Some(m), _) => Command::Delete(m),
+            (Some('d'), None, 'd') => Command::Delete(Motion::Line),
+            // like in vim, `cw` stops at the end of the word
+            (Some('c'), Some(Motion::NextWord), _) => Command::Change(Motion::WordEnd),
+            (Some('c'), Some(m), _) => Command::Change(m),
+            (Some('c'), None, 'c') => Command::Change(Motion::Line),
+            (Some(_), ..) => return None,
+            (None, Some(m), _) => Command::Move(m),
+            (None, None, 'd' | 'c') => {
+                self.pending = Some(c);
+                return None;
+            }
+            (None, None, 'x') => Command::Delete(Motion::Right),
+            (None, None, 'X') => Command::Delete(Motion::Left),
+            (None, None, 'D') => Command::Delete(Motion::End),
+            (None, None, 'C') => Command::Change(Motion::End),
+            (None, None, 'S') => Command::Change(Motion::Line),
+            (None, None, 's') => Command::Change(Motion::Right),
+            (None, None, 'i') => Command::Insert(None),
+            (None, None, 'a') => Command::Insert(Some(Motion::Right)),
+            (None, None, 'I') => Command::Insert(Some(Motion::Start)),
+            (None, None, 'A') => Command::Insert(Some(Motion::End)),
+            (None, None, 'p') => Command::Paste,
+            (None, None, 'u') => Command::Undo,
+            (None, None, 'k') => Command::HistoryPrev,
+            (None, None, 'j') => Command::HistoryNext,
+            (None, None, '.') => Command::Repeat,
+            _ => return None,
+        };
+        if let Command::Delete(_) | Command::Change(_) | Command::Insert(_) = cmd {
+            self.last_change = Some((cmd, String::new()));
+            self.recording = !matches!(cmd, Command::Delete(_));
+        }
+        Some(cmd)
+    }
+
+    /// Leaves insert mode or cancels a pending operator.
+    pub fn escape(&mut self) {
+        self.mode = Mode::Normal;
+        self.pending = None;
+        self.recording = false;
+    }
+
+    /// Starts over in insert mode, as after evaluating the input.
+    pub fn reset(&mut self) {
+        self.escape();
+        self.mode = Mode::Insert;
+    }
+
+    /// Remembers text typed in insert mode so that `.` can repeat it, `None` being a backspace.
+    pub fn record(&mut self, c: Option<char>) {
+        if let (true, Some((_, text))) = (self.recording, &mut self.last_change) {
+            match c {
+                Some(c) => text.push(c),
+                None => _ = text.pop(),
+            }
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Class {
+    Space,
+    Word,
+    Punct,
+}
+
+fn class(c: char) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punct
+    }
+}
+
+/// Where `m` moves the cursor to from `pos`.
+pub fn target(s: &str, pos: usize, m: Motion) -> usize {
+    let class_at = |i: usize| s[i..].chars().next().map(class);
+    match m {
+        Motion::Left => prev_grapheme(s, pos),
+        Motion::Right => next_grapheme(s, pos),
+        Motion::NextWord => {
+            let mut i = pos;
+            let start = class_at(i);
+            while i < s.len() && class_at(i) == start {
+                i = next_grapheme(s, i);
+            }
+            while class_at(i) == Some(Class::Space) {
+                i = next_grapheme(s, i);
+            }
+            i
+        }
+        Motion::PrevWord => {
+            let mut i = prev_grapheme(s, pos);
+            while i > 0 && class_at(i) == Some(Class::Space) {
+                i = prev_grapheme(s, i);
+            }
+            let end = class_at(i);
+            while i > 0 && class_at(prev_grapheme(s, i)) == end {
+                i = prev_grapheme(s, i);
+            }
+            i
+        }
+        Motion::WordEnd => {
+            let mut i = next_grapheme(s, pos);
+            while class_at(i) == Some(Class::Space) {
+                i = next_grapheme(s, i);
+            }
+            let start = class_at(i);
+            while next_grapheme(s, i) < s.len() && class_at(next_grapheme(s, i)) == start {
+                i = next_grapheme(s, i);
+            }
+            i.min(prev_grapheme(s, s.len()))
+        }
+        Motion::Start | Motion::Line => 0,
+        Motion::End => s.len(),
+    }
+}
+
+/// The text an operator with `m` acts on.
+pub fn range(s: &str, pos: usize, m: Motion) -> Range<usize> {
+    match m {
+        Motion::Line => 0..s.len(),
+        // `e` includes the character it lands on
+        Motion::WordEnd => pos..next_grapheme(s, target(s, pos, m)),
+        _ => {
+            let to = target(s, pos, m);
+            pos.min(to)..pos.max(to)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(vi: &mut Vi, keys: &str) -> Vec<Command> {
+        keys.chars().filter_map(|c| vi.normal_key(c)).collect()
+    }
+
+    #[test]
+    fn parses_keys() {
+        let mut vi = Vi::default();
+        assert_eq!(
+            keys(&mut vi, "wdwcwxddq."),
+            [
+                Command::Move(Motion::NextWord),
+                Command::Delete(Motion::NextWord),
+                Command::Change(Motion::WordEnd),
+                Command::Delete(Motion::Right),
+                Command::Delete(Motion::Line),
+                Command::Repeat,
+            ]
+        );
+        assert_eq!(
+            vi.last_change,
+            Some((Command::Delete(Motion::Line), "".into()))
+        );
+    }
+
+    #[test]
+    fn motions() {
+        let s = "foo(x, 2)  + bar";
+        assert_eq!(target(s, 0, Motion::NextWord), 3);
+        assert_eq!(target(s, 3, Motion::NextWord), 4);
+        assert_eq!(target(s, 8, Motion::NextWord), 11);
+        assert_eq!(target(s, 13, Motion::NextWord), s.len());
+        assert_eq!(target(s, 13, Motion::PrevWord), 11);
+        assert_eq!(target(s, 11, Motion::PrevWord), 8);
+        assert_eq!(target(s, 2, Motion::PrevWord), 0);
+        assert_eq!(target(s, 0, Motion::WordEnd), 2);
+        assert_eq!(target(s, 11, Motion::WordEnd), 15);
+        assert_eq!(range(s, 0, Motion::WordEnd), 0..3);
+        assert_eq!(range(s, 4, Motion::NextWord), 4..5);
+        assert_eq!(range(s, 4, Motion::Left), 3..4);
+    }
+}